@@ -0,0 +1,356 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::slice;
+
+use im_rc::Vector;
+
+use parser::Node;
+use interpreter::{Interpreter, Value};
+use symbol::{self, Symbol};
+use macros;
+use params::{self, Params, Pattern};
+
+// The analyzer runs before a top level expression is evaluated: it expands
+// macros and resolves every symbol to a local variable (depth, index) or to
+// a global slot, so unknown symbols are reported before anything runs.
+// Special forms, `def` names and `fn` parameters stay symbols, `defmacro`
+// and `import` are not analyzed at all. The target of `set!` becomes the
+// variable it assigns to, `def` is only allowed outside of functions.
+// The bodies of `try` become functions that the evaluator calls.
+struct Analyzer<'a> {
+	interpreter: &'a mut Interpreter,
+	// parameters of the enclosing functions, innermost last
+	scopes: Vec<Vec<Symbol>>,
+	// how many of the scopes are bodies of `try`, not functions
+	inline: usize
+}
+
+pub fn analyze(interpreter: &mut Interpreter, node: &Rc<Node>) -> Result<Rc<Node>, String> {
+	let mut analyzer = Analyzer { interpreter, scopes: vec![], inline: 0 };
+	analyzer.analyze(node)
+}
+
+// Declares the globals defined by the top level `def`s of a file (also inside
+// of `do` and macros like `defn`), so functions can refer to globals that are
+// defined further down.
+pub fn declare(interpreter: &mut Interpreter, nodes: &[Rc<Node>]) {
+	for node in nodes {
+		let items: Vec<Rc<Node>> = match **node {
+			Node::Expr(ref items) => items.iter().cloned().collect(),
+			_ => { continue; }
+		};
+		let symbol = match items.first().map(|node| &**node) {
+			Some(&Node::Symbol(symbol)) => symbol,
+			_ => { continue; }
+		};
+
+		match (symbol, items.get(1).map(|node| &**node)) {
+			(symbol::DEF, Some(&Node::Symbol(name))) => { interpreter.declare(name); },
+			(symbol::DO, _) => declare(interpreter, &items[1..]),
+			_ => {
+				if let Some(mac) = find_macro(interpreter, symbol) {
+					if let Ok(expansion) = macros::expand(&mac, items[1..].to_vec()) {
+						declare(interpreter, &[expansion]);
+					}
+				}
+			}
+		}
+	}
+}
+
+fn find_macro(interpreter: &Interpreter, symbol: Symbol) -> Option<Vec<macros::Clause>> {
+	let value = interpreter.resolve(symbol).and_then(|slot| interpreter.global(slot).ok());
+	match value.as_deref() {
+		Some(Value::Macro(mac)) => Some(mac.clauses.clone()),
+		_ => None
+	}
+}
+
+fn expr(items: Vec<Rc<Node>>) -> Rc<Node> {
+	Rc::new(Node::Expr(items.into_iter().collect()))
+}
+
+impl<'a> Analyzer<'a> {
+	fn analyze(&mut self, node: &Rc<Node>) -> Result<Rc<Node>, String> {
+		match **node {
+			Node::Symbol(symbol) => self.variable(symbol),
+			Node::List(ref items) => Ok(Rc::new(Node::List(self.analyze_all(items.iter())?))),
+			Node::Set(ref items) => Ok(Rc::new(Node::Set(self.analyze_all(items.iter())?))),
+			Node::Map(ref map) => {
+				let mut res: BTreeMap<String, Rc<Node>> = BTreeMap::new();
+				for (key, node) in map {
+					res.insert(key.clone(), self.analyze(node)?);
+				}
+				Ok(Rc::new(Node::Map(res)))
+			},
+			Node::Expr(ref items) => self.analyze_expr(node, items),
+			_ => Ok(node.clone())
+		}
+	}
+
+	fn analyze_all<'b, I: Iterator<Item=&'b Rc<Node>>>(&mut self, nodes: I) -> Result<Vector<Rc<Node>>, String> {
+		let mut res: Vector<Rc<Node>> = Vector::new();
+		for node in nodes {
+			res.push_back(self.analyze(node)?);
+		}
+		Ok(res)
+	}
+
+	fn local(&self, symbol: Symbol) -> Option<(usize, usize)> {
+		for (depth, scope) in self.scopes.iter().rev().enumerate() {
+			// the last of two parameters with the same name wins
+			if let Some(index) = scope.iter().rposition(|&param| param == symbol) {
+				return Some((depth, index));
+			}
+		}
+		None
+	}
+
+	fn variable(&mut self, symbol: Symbol) -> Result<Rc<Node>, String> {
+		if let Some((depth, index)) = self.local(symbol) {
+			return Ok(Rc::new(Node::Local(depth, index)));
+		}
+		match self.interpreter.resolve(symbol) {
+			Some(slot) => Ok(Rc::new(Node::Global(slot))),
+			None => Err(format!("unknown symbol `{}`", symbol))
+		}
+	}
+
+	fn analyze_expr(&mut self, node: &Rc<Node>, items: &Vector<Rc<Node>>) -> Result<Rc<Node>, String> {
+		// malformed expressions are left to the evaluator to report
+		let symbol = match items.front().map(|node| &**node) {
+			Some(&Node::Symbol(symbol)) => symbol,
+			Some(_) => { return Ok(Rc::new(Node::Expr(self.analyze_all(items.iter())?))); },
+			None => { return Ok(node.clone()); }
+		};
+
+		match symbol {
+			symbol::DEFMACRO | symbol::IMPORT => { return Ok(node.clone()); },
+			symbol::FN => { return self.analyze_fn(node, items); },
+			symbol::DEF => { return self.analyze_def(node, items); },
+			symbol::SET => { return self.analyze_set(node, items); },
+			symbol::TRY => { return self.analyze_try(items); },
+			symbol::LET => { return self.analyze_let(items); },
+			symbol::MATCH => { return self.analyze_match(items); },
+			symbol::DO | symbol::IF | symbol::AND | symbol::OR | symbol::APPLY => {
+				let mut res = self.analyze_all(items.iter().skip(1))?;
+				res.push_front(items.front().unwrap().clone());
+				return Ok(Rc::new(Node::Expr(res)));
+			},
+			_ => {}
+		}
+
+		if self.local(symbol).is_none() {
+			if let Some(mac) = find_macro(self.interpreter, symbol) {
+				let expansion = macros::expand(&mac, items.iter().skip(1).cloned().collect())?;
+				return self.analyze(&expansion);
+			}
+		}
+		Ok(Rc::new(Node::Expr(self.analyze_all(items.iter())?)))
+	}
+
+	// (fn {params} body...) or (fn ({params} body...) ...), the result always
+	// has one clause per arity, defaults are analyzed in the scope of the
+	// function
+	fn analyze_fn(&mut self, node: &Rc<Node>, items: &Vector<Rc<Node>>) -> Result<Rc<Node>, String> {
+		let args: Vec<Rc<Node>> = items.iter().skip(1).cloned().collect();
+		let form = match params::parse_fn(&args) {
+			Ok(form) => form,
+			Err(_) => { return Ok(node.clone()); }
+		};
+
+		let mut arities: Vec<(Params, Vec<Rc<Node>>)> = vec![];
+		for (mut params, body) in form.arities {
+			self.scopes.push(params.names.clone());
+			let defaults: Result<Vec<Rc<Node>>, String> = params.defaults.iter().map(|node| self.analyze(node)).collect();
+			let body = defaults.and_then(|defaults| {
+				params.defaults = defaults;
+				self.analyze_all(body.iter())
+			});
+			self.scopes.pop();
+			arities.push((params, body?.into_iter().collect()));
+		}
+		Ok(Rc::new(params::to_node(&form.doc, arities)))
+	}
+
+	// (def name x), name is declared first so that x can refer to it
+	fn analyze_def(&mut self, node: &Rc<Node>, items: &Vector<Rc<Node>>) -> Result<Rc<Node>, String> {
+		let items: Vec<Rc<Node>> = items.iter().cloned().collect();
+		match (items.len(), items.get(1).map(|node| &**node)) {
+			(3, Some(&Node::Symbol(name))) => {
+				if self.scopes.len() > self.inline {
+					return Err(format!("cannot def `{}` inside of a function, use set! to assign to a variable", name));
+				}
+				self.interpreter.declare(name);
+			},
+			_ => { return Ok(node.clone()); }
+		}
+
+		let mut res: Vector<Rc<Node>> = Vector::new();
+		res.push_back(items[0].clone());
+		res.push_back(items[1].clone());
+		res.push_back(self.analyze(&items[2])?);
+		Ok(Rc::new(Node::Expr(res)))
+	}
+
+	// (set! name x), name is the nearest parameter or global called name
+	fn analyze_set(&mut self, node: &Rc<Node>, items: &Vector<Rc<Node>>) -> Result<Rc<Node>, String> {
+		let items: Vec<Rc<Node>> = items.iter().cloned().collect();
+		let name = match (items.len(), items.get(1).map(|node| &**node)) {
+			(3, Some(&Node::Symbol(name))) => name,
+			_ => { return Ok(node.clone()); }
+		};
+
+		let target = match self.local(name) {
+			Some((depth, index)) => Node::Local(depth, index),
+			None => match self.interpreter.resolve(name) {
+				Some(slot) => Node::Global(slot),
+				None => { return Err(format!("cannot set! `{}`, it is not defined", name)); }
+			}
+		};
+
+		let mut res: Vector<Rc<Node>> = Vector::new();
+		res.push_back(items[0].clone());
+		res.push_back(Rc::new(target));
+		res.push_back(self.analyze(&items[2])?);
+		Ok(Rc::new(Node::Expr(res)))
+	}
+
+	// (try body... (catch kind e handler...)... (finally cleanup...)) becomes
+	// (try (fn {} body...) (catch kind (fn {e} handler...))... (finally (fn {} cleanup...)))
+	fn analyze_try(&mut self, items: &Vector<Rc<Node>>) -> Result<Rc<Node>, String> {
+		let mut body: Vec<Rc<Node>> = vec![];
+		let mut clauses: Vec<Rc<Node>> = vec![];
+		let mut finally = false;
+		for item in items.iter().skip(1) {
+			let clause: Vec<Rc<Node>> = match **item {
+				Node::Expr(ref clause) => clause.iter().cloned().collect(),
+				_ => vec![]
+			};
+			match (clause.first().map(|node| &**node), clause.get(2).map(|node| &**node)) {
+				(Some(&Node::Symbol(symbol::CATCH)), Some(&Node::Symbol(_))) if !finally => {
+					let handler = self.analyze_inline(vec![clause[2].clone()], &clause[3..])?;
+					let kind = self.analyze(&clause[1])?;
+					clauses.push(expr(vec![clause[0].clone(), kind, handler]));
+				},
+				(Some(&Node::Symbol(symbol::FINALLY)), _) if !finally => {
+					finally = true;
+					let cleanup = self.analyze_inline(vec![], &clause[1..])?;
+					clauses.push(expr(vec![clause[0].clone(), cleanup]));
+				},
+				(Some(&Node::Symbol(symbol::CATCH)), _) | (Some(&Node::Symbol(symbol::FINALLY)), _) => {
+					return Err("illegal try syntax".to_string());
+				},
+				_ if !clauses.is_empty() => { return Err("illegal try syntax".to_string()); },
+				_ => body.push(item.clone())
+			}
+		}
+
+		let mut res = vec![items[0].clone(), self.analyze_inline(vec![], &body)?];
+		res.extend(clauses);
+		Ok(expr(res))
+	}
+
+	// (let {pattern x ...} body...) becomes ((fn {pattern} (let {...} body...)) x),
+	// so every binding is a parameter and sees the ones before it
+	fn analyze_let(&mut self, items: &Vector<Rc<Node>>) -> Result<Rc<Node>, String> {
+		let bindings: Vec<Rc<Node>> = match items.get(1).map(|node| &**node) {
+			Some(Node::List(bindings)) if bindings.len() % 2 == 0 => bindings.iter().cloned().collect(),
+			_ => { return Err("illegal let syntax".to_string()); }
+		};
+		let body: Vec<Rc<Node>> = items.iter().skip(2).cloned().collect();
+		if bindings.is_empty() {
+			let mut res = vec![Rc::new(Node::Symbol(symbol::DO))];
+			res.extend(body);
+			return self.analyze(&expr(res));
+		}
+		match *bindings[0] {
+			Node::Symbol(symbol::AMPERSAND) => { return Err("illegal let syntax".to_string()); },
+			Node::Symbol(_) | Node::List(_) | Node::Map(_) => {},
+			_ => { return Err("illegal let syntax".to_string()); }
+		}
+
+		let mut rest = vec![items[0].clone(), Rc::new(Node::List(bindings[2..].iter().cloned().collect()))];
+		rest.extend(body);
+		let value = self.analyze(&bindings[1])?;
+		let function = self.analyze_inline(vec![bindings[0].clone()], &[expr(rest)])?;
+		Ok(expr(vec![function, value]))
+	}
+
+	// (match x pattern body pattern :when guard body ...) becomes
+	// (match x (fn {pattern} body) nil (fn {pattern} body) (fn {pattern} guard) ...)
+	fn analyze_match(&mut self, items: &Vector<Rc<Node>>) -> Result<Rc<Node>, String> {
+		let mut res = match items.get(1) {
+			Some(value) => vec![items[0].clone(), self.analyze(value)?],
+			None => { return Err("illegal match syntax".to_string()); }
+		};
+		let mut iter = items.iter().skip(2);
+		while let Some(pattern) = iter.next() {
+			let parsed = Pattern::parse(pattern)?;
+			let mut body = iter.next();
+			let mut guard: Option<&Rc<Node>> = None;
+			if let Some(Node::Keyword(keyword)) = body.map(|node| &**node) {
+				if keyword == "when" {
+					guard = iter.next();
+					body = iter.next();
+				}
+			}
+			let body = match body {
+				Some(body) => body,
+				None => { return Err(format!("illegal match syntax: no body for pattern {}", parsed)); }
+			};
+			res.push(self.analyze_inline(vec![pattern.clone()], slice::from_ref(body))?);
+			res.push(match guard {
+				Some(guard) => self.analyze_inline(vec![pattern.clone()], slice::from_ref(guard))?,
+				None => Rc::new(Node::Nil)
+			});
+		}
+		Ok(expr(res))
+	}
+
+	// an analyzed (fn {params} body...) that is called right away
+	fn analyze_inline(&mut self, params: Vec<Rc<Node>>, body: &[Rc<Node>]) -> Result<Rc<Node>, String> {
+		let mut items = vec![
+			Rc::new(Node::Symbol(symbol::FN)),
+			Rc::new(Node::List(params.into_iter().collect()))
+		];
+		items.extend(body.iter().cloned());
+		self.inline += 1;
+		let res = self.analyze(&expr(items));
+		self.inline -= 1;
+		res
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use testing::{for_each_backend, ok};
+
+	#[test]
+	fn unknown_symbols() {
+		for_each_backend(|eval| {
+			assert_eq!(eval("(do (def side 1) (fn {} (undefined)))"), Err("unknown symbol `undefined`".to_string()));
+			assert_eq!(eval("side"), Err("`side` is not defined yet".to_string()));
+			assert_eq!(eval("(fn {x} y)"), Err("unknown symbol `y`".to_string()));
+		});
+	}
+
+	#[test]
+	fn forward_references() {
+		for_each_backend(|eval| {
+			assert_eq!(eval("(defn even? {n} (if (zero? n) true (odd? (dec n))))
+				(defn odd? {n} (if (zero? n) false (even? (dec n))))
+				(even? 10)"), ok("true"));
+			assert_eq!(eval("(defn f {} later) (f) (def later 1)"), Err("`later` is not defined yet".to_string()));
+		});
+	}
+
+	#[test]
+	fn lexical_scope() {
+		for_each_backend(|eval| {
+			// `f` sees the global `x`, not the parameter of its caller
+			assert_eq!(eval("(def x 1) (defn f {} x) (defn g {x} (f)) (g 2)"), ok("1"));
+			assert_eq!(eval("(defn f {x} ((fn {x} x) (+ x 1))) (f 1)"), ok("2"));
+		});
+	}
+}
@@ -0,0 +1,99 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use interpreter::{Value, EvalResult, Builtins};
+
+// Atoms are mutable references to (immutable) values, e.g. for counters and
+// caches. Copies of an atom share its value.
+pub const BUILTINS: &Builtins = &[
+	("atom", atom, "(atom x) Returns a new atom holding x."),
+	("deref", deref, "(deref atom) The value of atom, also written @atom."),
+	("reset!", reset, "(reset! atom x) Sets the value of atom to x and returns x."),
+	("compare-and-set!", compare_and_set, "(compare-and-set! atom old new) Sets the value of atom to new if it is equal to old, returns whether it did.")
+];
+
+// Builtins cannot call functions, so `swap!` is written in Lial. It is
+// defined together with the builtins, also without the prelude.
+pub const SWAP: &str = "(def swap! (fn {a f & args} \"Sets the value of the atom a to (f @a args...) and returns it.\"
+	(reset! a (apply f @a args))))";
+
+fn atom_arg<'a>(name: &str, value: &'a Value) -> Result<&'a RefCell<Rc<Value>>, String> {
+	match value {
+		Value::Atom(cell) => Ok(cell),
+		_ => Err(format!("{} takes an atom, got {}", name, value.type_name()))
+	}
+}
+
+pub fn atom(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 1 {
+		return Err("atom takes only one argument".into());
+	}
+
+	Ok(Rc::new(Value::Atom(Rc::new(RefCell::new(args[0].clone())))))
+}
+
+pub fn deref(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 1 {
+		return Err("deref takes only one argument of type atom".into());
+	}
+
+	let cell = atom_arg("deref", &args[0])?;
+	let value = cell.borrow().clone();
+	Ok(value)
+}
+
+pub fn reset(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 2 {
+		return Err("reset! takes an atom and a value".into());
+	}
+
+	let cell = atom_arg("reset!", &args[0])?;
+	cell.replace(args[1].clone());
+	Ok(args[1].clone())
+}
+
+pub fn compare_and_set(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 3 {
+		return Err("compare-and-set! takes an atom, the expected and the new value".into());
+	}
+
+	let cell = atom_arg("compare-and-set!", &args[0])?;
+	if *cell.borrow() != args[1] {
+		return Ok(Rc::new(Value::Bool(false)));
+	}
+	cell.replace(args[2].clone());
+	Ok(Rc::new(Value::Bool(true)))
+}
+
+#[cfg(test)]
+mod tests {
+	use interpreter::Interpreter;
+	use testing::{for_each_backend, ok};
+
+	#[test]
+	fn atoms() {
+		for_each_backend(|eval| {
+			assert_eq!(eval("(def counter (atom 0)) (reset! counter 2) @counter"), ok("2"));
+			assert_eq!(eval("(swap! counter inc) (swap! counter (fn {n} (* n 10))) (deref counter)"), ok("30"));
+			assert_eq!(eval("(list (compare-and-set! counter 1 5) (compare-and-set! counter 30 5) @counter)"), ok("{ false true 5 }"));
+
+			// copies share the value, a function can keep state in a closed over atom
+			assert_eq!(eval("(def counter2 counter) (reset! counter2 7) @counter"), ok("7"));
+			assert_eq!(eval("(defn make-counter {} ((fn {n} (fn {} (swap! n inc))) (atom 0))) (def next (make-counter)) (next) (next) (next)"), ok("3"));
+
+			assert_eq!(eval("(= counter counter2 (atom 7))"), ok("false"));
+			assert_eq!(eval("(count #{ counter counter2 (atom 7) })"), ok("2"));
+			assert_eq!(eval("counter"), ok("<Atom 7>"));
+			assert_eq!(eval("(atom (atom (list 1 (atom 2))))"), ok("<Atom <Atom { 1 <Atom 2> }>>"));
+			assert_eq!(eval("(def me (atom 0)) (reset! me me) me"), ok("<Atom <Atom>>"));
+			assert_eq!(eval("(reset! me (list 1 me)) (list me)"), ok("{ <Atom { 1 <Atom> }> }"));
+			assert_eq!(eval("(swap! counter + 1 2 3 4)"), ok("17"));
+			assert_eq!(eval("(swap! counter list :a :b :c) @counter"), ok("{ 17 :a :b :c }"));
+			assert_eq!(eval("@5"), Err("deref takes an atom, got int".to_string()));
+		});
+
+		// swap! does not depend on the prelude
+		let mut interpreter = Interpreter::without_prelude();
+		assert_eq!(interpreter.eval_str("(def a (atom 1)) (swap! a + 2) (swap! a (fn {n} (* n 10)))").unwrap().to_string(), "30");
+	}
+}
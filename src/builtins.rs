@@ -1,5 +1,17 @@
+// Kept in the style of the original builtins.
+#![allow(clippy::match_ref_pats, clippy::needless_return, clippy::while_let_on_iterator)]
+
 use std::rc::Rc;
-use interpreter::{Value, EvalResult};
+use interpreter::{Value, EvalResult, Builtins};
+
+pub const BUILTINS: &Builtins = &[
+	("+", add, "(+ x ...) Sums up ints and reals."),
+	("-", sub, "(- x ...) Subtracts all other arguments from x, negates a single argument."),
+	("*", mul, "(* x ...) Multiplies ints and reals."),
+	("=", equals, "(= x y ...) Whether all arguments are equal to x."),
+	("hex", hex, "(hex n) Formats the int n as hexadecimal string."),
+	("bin", bin, "(bin n) Formats the int n as binary string.")
+];
 
 pub fn add(args: Vec<Rc<Value>>) -> EvalResult {
 	let mut res = Value::Int(0);
@@ -10,7 +22,7 @@ pub fn add(args: Vec<Rc<Value>>) -> EvalResult {
 			(Value::Int(a), &Value::Real(b)) => Value::Real((a as f64) + b),
 			(Value::Real(a), &Value::Real(b)) => Value::Real(a + b),
 			_ => {
-				return Err("`+` takes arguments of type int or real".to_string());
+				return Err("`+` takes arguments of type int or real".into());
 			}
 		};
 	}
@@ -26,7 +38,7 @@ pub fn mul(args: Vec<Rc<Value>>) -> EvalResult {
 			(Value::Int(a), &Value::Real(b)) => Value::Real((a as f64) * b),
 			(Value::Real(a), &Value::Real(b)) => Value::Real(a * b),
 			_ => {
-				return Err("`*` takes arguments of type int or real".to_string());
+				return Err("`*` takes arguments of type int or real".into());
 			}
 		};
 	}
@@ -38,14 +50,14 @@ pub fn sub(args: Vec<Rc<Value>>) -> EvalResult {
 		return match args[0].as_ref() {
 			&Value::Int(i) => Ok(Rc::new(Value::Int(-i))),
 			&Value::Real(r) => Ok(Rc::new(Value::Real(-r))),
-			_ => { return Err("`-` takes arguments of type int or real".to_string()); }
+			_ => { return Err("`-` takes arguments of type int or real".into()); }
 		};
 	} else if args.len() > 1 {
 		let mut iter = args.into_iter();
 		let mut res = match iter.next().unwrap().as_ref() {
 			&Value::Int(i) => Value::Int(i),
 			&Value::Real(r) => Value::Real(r),
-			_ => { return Err("`-` takes arguments of type int or real".to_string()); }
+			_ => { return Err("`-` takes arguments of type int or real".into()); }
 		};
 		while let Some(arg) = iter.next() {
 			res = match (res, arg.as_ref()) {
@@ -54,13 +66,13 @@ pub fn sub(args: Vec<Rc<Value>>) -> EvalResult {
 				(Value::Int(a), &Value::Real(b)) => Value::Real((a as f64) - b),
 				(Value::Real(a), &Value::Real(b)) => Value::Real(a - b),
 				_ => {
-					return Err("`-` takes arguments of type int or real".to_string());
+					return Err("`-` takes arguments of type int or real".into());
 				}
 			};
 		}
 		return Ok(Rc::new(res));
 	} else {
-		return Err("`-` takes min. one argument of type int or real".to_string());
+		return Err("`-` takes min. one argument of type int or real".into());
 	}
 }
 
@@ -74,36 +86,28 @@ pub fn equals(args: Vec<Rc<Value>>) -> EvalResult {
 		}
 		Ok(Rc::new(Value::Bool(true)))
 	} else {
-		Err("`=` takes min. one argument".to_string())
-	}
-}
-
-pub fn echo(args: Vec<Rc<Value>>) -> EvalResult {
-	for arg in args {
-		print!("{}", arg.to_string());
+		Err("`=` takes min. one argument".into())
 	}
-	println!();
-	Ok(Rc::new(Value::Nil))
 }
 
 pub fn bin(args: Vec<Rc<Value>>) -> EvalResult {
 	if args.len() != 1 {
-		return Err("bin takes only one argument of type int".to_string());
+		return Err("bin takes only one argument of type int".into());
 	}
 
 	match *args[0] {
 		Value::Int(i) => Ok(Rc::new( Value::Str( format!("{:b}", i) ) )),
-		_ => Err("bin only takes int as argument".to_string())
+		_ => Err("bin only takes int as argument".into())
 	}
 }
 
 pub fn hex(args: Vec<Rc<Value>>) -> EvalResult {
 	if args.len() != 1 {
-		return Err("hex takes only one argument of type int".to_string());
+		return Err("hex takes only one argument of type int".into());
 	}
 
 	match *args[0] {
 		Value::Int(i) => Ok(Rc::new( Value::Str( format!("{:X}", i) ) )),
-		_ => Err("hex only takes int as argument".to_string())
+		_ => Err("hex only takes int as argument".into())
 	}
 }
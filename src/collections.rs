@@ -0,0 +1,58 @@
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::BuildHasherDefault;
+use std::rc::Rc;
+
+use im_rc;
+
+use interpreter::Value;
+
+// Maps and sets are persistent hash array mapped tries: adding or removing a
+// key returns a new map in O(log n) that shares most of its nodes with the
+// old one. They hash with fixed keys instead of a random seed, so their
+// iteration order is the same in every run.
+pub type Hasher = BuildHasherDefault<DefaultHasher>;
+pub type Map<K, V> = im_rc::HashMap<K, V, Hasher>;
+pub type Set<T> = im_rc::HashSet<T, Hasher>;
+
+// The entries of map sorted by key: the order maps are printed, serialized
+// and iterated in, independent of the hash order of the trie.
+pub fn sorted<V>(map: &Map<String, V>) -> Vec<(&String, &V)> {
+	let mut entries: Vec<(&String, &V)> = map.iter().collect();
+	entries.sort_by(|a, b| a.0.cmp(b.0));
+	entries
+}
+
+// The items of set in the order they are printed and serialized in: nil,
+// bools, numbers (by value), strings, keywords, lists, maps, sets and the
+// rest, values of the same kind by their printed form.
+pub fn sorted_set(set: &Set<Rc<Value>>) -> Vec<&Rc<Value>> {
+	let mut items: Vec<&Rc<Value>> = set.iter().collect();
+	items.sort_by(|a, b| order(a, b));
+	items
+}
+
+fn rank(value: &Value) -> u8 {
+	match value {
+		&Value::Nil => 0,
+		&Value::Bool(_) => 1,
+		&Value::Int(_) | &Value::Real(_) => 2,
+		&Value::Str(_) => 3,
+		&Value::Keyword(_) => 4,
+		&Value::List(_) => 5,
+		&Value::Map(_) => 6,
+		&Value::Set(_) => 7,
+		_ => 8
+	}
+}
+
+fn order(a: &Value, b: &Value) -> Ordering {
+	match (a, b) {
+		(&Value::Int(a), &Value::Int(b)) => a.cmp(&b),
+		(&Value::Int(a), &Value::Real(b)) => (a as f64).partial_cmp(&b).unwrap_or(Ordering::Less),
+		(&Value::Real(a), &Value::Int(b)) => a.partial_cmp(&(b as f64)).unwrap_or(Ordering::Greater),
+		(&Value::Real(a), &Value::Real(b)) => a.partial_cmp(&b).unwrap_or_else(|| a.is_nan().cmp(&b.is_nan())),
+		_ if rank(a) != rank(b) => rank(a).cmp(&rank(b)),
+		_ => a.to_string().cmp(&b.to_string())
+	}
+}
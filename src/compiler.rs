@@ -0,0 +1,525 @@
+use std::rc::Rc;
+
+use parser::Node;
+use interpreter::Value;
+use symbol::{self, Symbol};
+use params::{self, Params};
+
+// Instructions of the stack machine in `vm`. Arguments are indices into the
+// tables of the `Proto` the instruction belongs to, jump targets are indices
+// into its code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+	Const(usize),
+	// a parameter of the running function
+	Local(usize),
+	// pop a value into a parameter
+	SetLocal(usize),
+	// jump over the default if the argument was passed
+	Default(usize, usize),
+	// a value captured when the function was created
+	Captured(usize),
+	// a global slot
+	Global(usize),
+	Def(usize),
+	// assign the value on the stack to a global slot
+	SetGlobal(usize),
+	// bind the variables of the patterns of the parameters
+	Destructure,
+	// Parameters that are assigned by `set!` live in boxes shared with the
+	// closures that capture them: box a parameter, replace a box with its
+	// value, pop a value and a box and store the value in the box.
+	Box(usize),
+	Unbox,
+	Store,
+	List(usize),
+	// keys[i] are the keys, their values are on the stack
+	Map(usize),
+	Set(usize),
+	// a function of protos[i..i + n], one per arity
+	Closure(usize, usize),
+	Call(usize),
+	TailCall(usize),
+	Jump(usize),
+	// pop the condition and jump if it is false (or true)
+	JumpIfFalse(usize),
+	JumpIfTrue(usize),
+	Pop,
+	Return,
+	// evaluate nodes[i] with the tree-walker (defmacro, import)
+	Eval(usize),
+	// the body, kind and handler of every catch and the cleanup (if there
+	// is a finally) of a `try` are on the stack
+	Try(usize, bool),
+	// the value and the body and guard of every clause of a `match` are on
+	// the stack, leaves the selected body and the value to be called
+	Match(usize),
+	// the function and its n arguments are on the stack, the last argument
+	// is a list of more arguments
+	Apply(usize)
+}
+
+// Where a function finds a captured value when it is created: in a parameter
+// or in the captured values of the enclosing function.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+	Local(usize),
+	Captured(usize)
+}
+
+// A compiled function body (or top level expression).
+#[derive(Debug)]
+pub struct Proto {
+	pub params: Params,
+	pub code: Vec<Op>,
+	pub consts: Vec<Rc<Value>>,
+	pub names: Vec<Symbol>,
+	pub keys: Vec<Vec<String>>,
+	pub protos: Vec<Rc<Proto>>,
+	pub nodes: Vec<Rc<Node>>,
+	pub captures: Vec<Capture>,
+	// only used to create the `Lambda` of a closure
+	pub body: Vec<Rc<Node>>,
+	pub doc: Option<String>
+}
+
+impl Proto {
+	fn new(params: Params) -> Proto {
+		Proto {
+			params,
+			code: vec![],
+			consts: vec![],
+			names: vec![],
+			keys: vec![],
+			protos: vec![],
+			nodes: vec![],
+			captures: vec![],
+			body: vec![],
+			doc: None
+		}
+	}
+}
+
+struct Scope {
+	proto: Proto,
+	// lexical addresses (relative to this scope) of the captured values, in
+	// the order of `proto.captures`
+	captured: Vec<(usize, usize)>,
+	// the boxed parameters
+	assigned: Vec<usize>
+}
+
+enum Variable {
+	Local(usize),
+	Captured(usize)
+}
+
+pub struct Compiler {
+	scopes: Vec<Scope>
+}
+
+// Collects the parameters of a function that are assigned by `set!` in its
+// body, `depth` is the number of functions between nodes and the function.
+fn assigned(nodes: &[Rc<Node>], depth: usize, res: &mut Vec<usize>) {
+	for node in nodes {
+		let items: Vec<Rc<Node>> = match **node {
+			Node::Expr(ref items) | Node::List(ref items) | Node::Set(ref items) => items.iter().cloned().collect(),
+			Node::Map(ref map) => map.values().cloned().collect(),
+			_ => { continue; }
+		};
+		if let Node::Expr(_) = **node {
+			match (items.first().map(|node| &**node), items.get(1).map(|node| &**node)) {
+				(Some(&Node::Symbol(symbol::SET)), Some(&Node::Local(d, index))) if d == depth && !res.contains(&index) => {
+					res.push(index);
+				},
+				(Some(&Node::Symbol(symbol::FN)), _) => {
+					assigned(&items[1..], depth + 1, res);
+					continue;
+				},
+				_ => {}
+			}
+		}
+		assigned(&items, depth, res);
+	}
+}
+
+// Compiles an analyzed top level expression into a function without
+// parameters.
+pub fn compile(node: &Rc<Node>) -> Result<Proto, String> {
+	let mut compiler = Compiler {
+		scopes: vec![Scope { proto: Proto::new(Params::none()), captured: vec![], assigned: vec![] }]
+	};
+	compiler.compile(node, true)?;
+	compiler.emit(Op::Return);
+	Ok(compiler.scopes.pop().unwrap().proto)
+}
+
+impl Compiler {
+	fn proto(&mut self) -> &mut Proto {
+		&mut self.scopes.last_mut().unwrap().proto
+	}
+
+	fn emit(&mut self, op: Op) -> usize {
+		let proto = self.proto();
+		proto.code.push(op);
+		proto.code.len() - 1
+	}
+
+	// sets the target of the jump at `at` to the next instruction
+	fn patch(&mut self, at: usize) -> Result<(), String> {
+		let proto = self.proto();
+		let target = proto.code.len();
+		proto.code[at] = match proto.code[at] {
+			Op::Jump(_) => Op::Jump(target),
+			Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+			Op::JumpIfTrue(_) => Op::JumpIfTrue(target),
+			Op::Default(slot, _) => Op::Default(slot, target),
+			op => { return Err(format!("compiler: cannot patch {:?}", op)); }
+		};
+		Ok(())
+	}
+
+	fn constant(&mut self, value: Value) {
+		let proto = self.proto();
+		proto.consts.push(Rc::new(value));
+		let index = proto.consts.len() - 1;
+		self.emit(Op::Const(index));
+	}
+
+	fn name(&mut self, name: Symbol) -> usize {
+		let proto = self.proto();
+		if let Some(index) = proto.names.iter().position(|&other| other == name) {
+			return index;
+		}
+		proto.names.push(name);
+		proto.names.len() - 1
+	}
+
+	// Resolves the local variable (depth, index) of the analyzer as seen
+	// from `scope`: depth 0 are the parameters, everything else is captured
+	// from the enclosing functions when the closure is created.
+	fn resolve(&mut self, depth: usize, index: usize, scope: usize) -> Variable {
+		if depth == 0 {
+			return Variable::Local(index);
+		}
+		if let Some(pos) = self.scopes[scope].captured.iter().position(|&addr| addr == (depth, index)) {
+			return Variable::Captured(pos);
+		}
+
+		let capture = match self.resolve(depth - 1, index, scope - 1) {
+			Variable::Local(slot) => Capture::Local(slot),
+			Variable::Captured(pos) => Capture::Captured(pos)
+		};
+		let scope = &mut self.scopes[scope];
+		scope.captured.push((depth, index));
+		scope.proto.captures.push(capture);
+		Variable::Captured(scope.captured.len() - 1)
+	}
+
+	// whether the local variable (depth, index) as seen from the innermost
+	// scope is boxed
+	fn is_boxed(&self, depth: usize, index: usize) -> bool {
+		let scope = &self.scopes[self.scopes.len() - 1 - depth];
+		scope.assigned.contains(&index)
+	}
+
+	// pushes the local variable (depth, index), its box if it is boxed
+	fn load(&mut self, depth: usize, index: usize) {
+		let scope = self.scopes.len() - 1;
+		match self.resolve(depth, index, scope) {
+			Variable::Local(slot) => self.emit(Op::Local(slot)),
+			Variable::Captured(pos) => self.emit(Op::Captured(pos))
+		};
+	}
+
+	// `tail` is true if the value of node is returned by the function, calls
+	// in tail position do not grow the stack of the vm.
+	fn compile(&mut self, node: &Rc<Node>, tail: bool) -> Result<(), String> {
+		match **node {
+			Node::Str(ref value) => self.constant(Value::Str(value.clone())),
+			Node::Int(value) => self.constant(Value::Int(value)),
+			Node::Real(value) => self.constant(Value::Real(value)),
+			Node::Bool(value) => self.constant(Value::Bool(value)),
+			Node::Nil => self.constant(Value::Nil),
+			Node::Keyword(ref name) => self.constant(Value::Keyword(name.clone())),
+			Node::Local(depth, index) => {
+				self.load(depth, index);
+				if self.is_boxed(depth, index) {
+					self.emit(Op::Unbox);
+				}
+			},
+			Node::Global(slot) => { self.emit(Op::Global(slot)); },
+			Node::Symbol(name) => { return Err(format!("unknown symbol `{}`", name)); },
+			Node::List(ref nodes) => {
+				for node in nodes {
+					self.compile(node, false)?;
+				}
+				self.emit(Op::List(nodes.len()));
+			},
+			Node::Set(ref nodes) => {
+				for node in nodes {
+					self.compile(node, false)?;
+				}
+				self.emit(Op::Set(nodes.len()));
+			},
+			Node::Map(ref nodes) => {
+				let mut keys: Vec<String> = vec![];
+				for (key, node) in nodes {
+					self.compile(node, false)?;
+					keys.push(key.clone());
+				}
+				let proto = self.proto();
+				proto.keys.push(keys);
+				let index = proto.keys.len() - 1;
+				self.emit(Op::Map(index));
+			},
+			Node::Expr(ref items) => {
+				let items: Vec<Rc<Node>> = items.iter().cloned().collect();
+				return self.compile_expr(node, &items, tail);
+			}
+		}
+		Ok(())
+	}
+
+	fn compile_expr(&mut self, node: &Rc<Node>, items: &[Rc<Node>], tail: bool) -> Result<(), String> {
+		if items.is_empty() {
+			return Err("cannot evaluate empty expression".to_string());
+		}
+
+		if let Node::Symbol(symbol) = *items[0] {
+			let args = &items[1..];
+			match symbol {
+				symbol::FN => { return self.compile_fn(args); },
+				symbol::DEF => { return self.compile_def(args); },
+				symbol::SET => { return self.compile_set(args); },
+				symbol::TRY => { return self.compile_try(args); },
+				symbol::MATCH => { return self.compile_match(args, tail); },
+				symbol::APPLY => { return self.compile_apply(args); },
+				symbol::DO => { return self.compile_body(args, tail); },
+				symbol::IF => { return self.compile_if(args, tail); },
+				symbol::AND => { return self.compile_logical(args, true); },
+				symbol::OR => { return self.compile_logical(args, false); },
+				symbol::DEFMACRO | symbol::IMPORT => {
+					let proto = self.proto();
+					proto.nodes.push(node.clone());
+					let index = proto.nodes.len() - 1;
+					self.emit(Op::Eval(index));
+					return Ok(());
+				},
+				_ => {}
+			}
+		}
+
+		for item in items {
+			self.compile(item, false)?;
+		}
+		let argc = items.len() - 1;
+		self.emit(if tail { Op::TailCall(argc) } else { Op::Call(argc) });
+		Ok(())
+	}
+
+	fn compile_body(&mut self, nodes: &[Rc<Node>], tail: bool) -> Result<(), String> {
+		if nodes.is_empty() {
+			self.constant(Value::Nil);
+			return Ok(());
+		}
+
+		for (i, node) in nodes.iter().enumerate() {
+			let last = i == nodes.len() - 1;
+			self.compile(node, tail && last)?;
+			if !last {
+				self.emit(Op::Pop);
+			}
+		}
+		Ok(())
+	}
+
+	fn compile_fn(&mut self, args: &[Rc<Node>]) -> Result<(), String> {
+		let form = params::parse_fn(args)?;
+		let count = form.arities.len();
+		let mut first: Option<usize> = None;
+		for (i, (params, body)) in form.arities.into_iter().enumerate() {
+			let doc = if i == 0 { form.doc.clone() } else { None };
+			let index = self.compile_arity(params, body, doc)?;
+			first = first.or(Some(index));
+		}
+		self.emit(Op::Closure(first.unwrap(), count));
+		Ok(())
+	}
+
+	// Compiles one arity into a proto of the enclosing one, returns its index.
+	fn compile_arity(&mut self, params: Params, body: Vec<Rc<Node>>, doc: Option<String>) -> Result<usize, String> {
+		let mut boxed: Vec<usize> = vec![];
+		assigned(&params.defaults, 0, &mut boxed);
+		assigned(&body, 0, &mut boxed);
+		let defaults = params.defaults.clone();
+		let required = params.required;
+		let slots = params.slots();
+		let optional = required..required + defaults.len();
+		self.scopes.push(Scope { proto: Proto::new(params), captured: vec![], assigned: boxed.clone() });
+
+		// the defaults can capture and assign the parameters before them, so
+		// every parameter is boxed as soon as it is bound
+		for &slot in &boxed {
+			if slot < slots && !optional.contains(&slot) {
+				self.emit(Op::Box(slot));
+			}
+		}
+		let mut res = Ok(());
+		for (i, default) in defaults.iter().enumerate() {
+			let skip = self.emit(Op::Default(required + i, 0));
+			res = res.and_then(|_| self.compile(default, false));
+			self.emit(Op::SetLocal(required + i));
+			res = res.and_then(|_| self.patch(skip));
+			if boxed.contains(&(required + i)) {
+				self.emit(Op::Box(required + i));
+			}
+		}
+		if !self.proto().params.patterns.is_empty() {
+			self.emit(Op::Destructure);
+		}
+		for &slot in &boxed {
+			if slot >= slots {
+				self.emit(Op::Box(slot));
+			}
+		}
+		res = res.and_then(|_| self.compile_body(&body, true));
+		self.emit(Op::Return);
+		let mut proto = self.scopes.pop().unwrap().proto;
+		res?;
+		proto.body = body;
+		proto.doc = doc;
+
+		let parent = self.proto();
+		parent.protos.push(Rc::new(proto));
+		Ok(parent.protos.len() - 1)
+	}
+
+	fn compile_def(&mut self, args: &[Rc<Node>]) -> Result<(), String> {
+		match (args.len(), args.first().map(|node| &**node)) {
+			(2, Some(&Node::Symbol(name))) => {
+				self.compile(&args[1], false)?;
+				let index = self.name(name);
+				self.emit(Op::Def(index));
+				Ok(())
+			},
+			_ => Err("illegal def syntax".to_string())
+		}
+	}
+
+	fn compile_set(&mut self, args: &[Rc<Node>]) -> Result<(), String> {
+		if args.len() != 2 {
+			return Err("illegal set! syntax".to_string());
+		}
+
+		match *args[0] {
+			Node::Local(depth, index) if self.is_boxed(depth, index) => {
+				self.load(depth, index);
+				self.compile(&args[1], false)?;
+				self.emit(Op::Store);
+			},
+			Node::Global(slot) => {
+				self.compile(&args[1], false)?;
+				self.emit(Op::SetGlobal(slot));
+			},
+			Node::Symbol(name) => { return Err(format!("cannot set! `{}`, it is not defined", name)); },
+			_ => { return Err("illegal set! syntax".to_string()); }
+		}
+		Ok(())
+	}
+
+	// (try body (catch kind handler)... (finally cleanup)) as rewritten by the
+	// analyzer
+	fn compile_try(&mut self, args: &[Rc<Node>]) -> Result<(), String> {
+		if args.is_empty() {
+			return Err("illegal try syntax".to_string());
+		}
+
+		self.compile(&args[0], false)?;
+		let mut catches = 0;
+		let mut finally = false;
+		for node in &args[1..] {
+			let clause: Vec<Rc<Node>> = match **node {
+				Node::Expr(ref clause) => clause.iter().cloned().collect(),
+				_ => { return Err("illegal try syntax".to_string()); }
+			};
+			match (clause.len(), clause.first().map(|node| &**node)) {
+				(3, Some(&Node::Symbol(symbol::CATCH))) if !finally => {
+					self.compile(&clause[1], false)?;
+					self.compile(&clause[2], false)?;
+					catches += 1;
+				},
+				(2, Some(&Node::Symbol(symbol::FINALLY))) if !finally => {
+					self.compile(&clause[1], false)?;
+					finally = true;
+				},
+				_ => { return Err("illegal try syntax".to_string()); }
+			}
+		}
+		self.emit(Op::Try(catches, finally));
+		Ok(())
+	}
+
+	// the selected body is called like any other function, in tail position
+	// too
+	fn compile_match(&mut self, args: &[Rc<Node>], tail: bool) -> Result<(), String> {
+		if args.len().is_multiple_of(2) {
+			return Err("illegal match syntax".to_string());
+		}
+
+		for arg in args {
+			self.compile(arg, false)?;
+		}
+		self.emit(Op::Match(args.len() / 2));
+		self.emit(if tail { Op::TailCall(1) } else { Op::Call(1) });
+		Ok(())
+	}
+
+	fn compile_apply(&mut self, args: &[Rc<Node>]) -> Result<(), String> {
+		if args.len() < 2 {
+			return Err("illegal apply syntax".to_string());
+		}
+
+		for arg in args {
+			self.compile(arg, false)?;
+		}
+		self.emit(Op::Apply(args.len() - 1));
+		Ok(())
+	}
+
+	fn compile_if(&mut self, args: &[Rc<Node>], tail: bool) -> Result<(), String> {
+		if args.len() < 2 {
+			return Err("illegal if syntax".to_string());
+		}
+
+		self.compile(&args[0], false)?;
+		let to_else = self.emit(Op::JumpIfFalse(0));
+		self.compile(&args[1], tail)?;
+		let to_end = self.emit(Op::Jump(0));
+		self.patch(to_else)?;
+		match args.get(2) {
+			Some(node) => self.compile(node, tail)?,
+			None => self.constant(Value::Nil)
+		}
+		self.patch(to_end)?;
+		Ok(())
+	}
+
+	// `and` stops at the first false argument, `or` at the first true one
+	fn compile_logical(&mut self, args: &[Rc<Node>], and: bool) -> Result<(), String> {
+		let mut exits: Vec<usize> = vec![];
+		for node in args {
+			self.compile(node, false)?;
+			exits.push(self.emit(if and { Op::JumpIfFalse(0) } else { Op::JumpIfTrue(0) }));
+		}
+		self.constant(Value::Bool(and));
+		let to_end = self.emit(Op::Jump(0));
+		for exit in exits {
+			self.patch(exit)?;
+		}
+		self.constant(Value::Bool(!and));
+		self.patch(to_end)?;
+		Ok(())
+	}
+}
+
@@ -0,0 +1,86 @@
+//! Lial as a configuration language. A configuration file is evaluated in a
+//! sandboxed interpreter (see `Interpreter::sandboxed`) and its result is
+//! deserialized into a Rust type. The result is the value of `export` if the
+//! file defines it, otherwise the value of the last expression:
+//!
+//! ```
+//! #[macro_use]
+//! extern crate serde_derive;
+//! extern crate lial;
+//!
+//! #[derive(Deserialize)]
+//! struct Server { host: String, port: u16 }
+//!
+//! # fn main() {
+//! let server: Server = lial::config::from_str("
+//!     (def base 8000)
+//!     (def export { host: \"localhost\" port: (+ base 80) })
+//! ").unwrap();
+//! assert_eq!(server.port, 8080);
+//! # }
+//! ```
+
+use std::path::Path;
+use std::rc::Rc;
+
+use serde::de::DeserializeOwned;
+
+use interpreter::{Interpreter, Value};
+use de::from_value;
+
+/// Name of the definition that is used as the result of a configuration.
+pub const EXPORT: &str = "export";
+
+fn result<T: DeserializeOwned>(interpreter: &Interpreter, last: Rc<Value>) -> Result<T, String> {
+	let value = interpreter.get(EXPORT).unwrap_or(last);
+	from_value(&value).map_err(|e| e.to_string())
+}
+
+/// Evaluates `source` as a configuration.
+pub fn from_str<T: DeserializeOwned>(source: &str) -> Result<T, String> {
+	let mut interpreter = Interpreter::sandboxed();
+	let value = interpreter.eval_str(source).map_err(|e| e.to_string())?;
+	result(&interpreter, value)
+}
+
+/// Evaluates the file as a configuration, errors start with its path.
+pub fn from_file<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, String> {
+	let mut interpreter = Interpreter::sandboxed();
+	let value = interpreter.eval_file(path.as_ref()).map_err(|e| e.to_string())?;
+	result(&interpreter, value).map_err(|e| format!("{}: {}", path.as_ref().display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+	use std::fs;
+
+	#[derive(Debug, PartialEq, Deserialize)]
+	struct Server {
+		host: String,
+		port: u16,
+		workers: Option<u32>
+	}
+
+	#[test]
+	fn config() {
+		let server: Server = from_str("{ host: \"localhost\" port: (* 80 101) }").unwrap();
+		assert_eq!(server, Server { host: "localhost".to_string(), port: 8080, workers: None });
+
+		let source = "(def export { host: \"a\" port: 1 workers: 4 }) \"ignored\"";
+		assert_eq!(from_str::<Server>(source).unwrap().workers, Some(4));
+
+		assert_eq!(from_str::<Server>("{ host: \"a\" }"), Err("missing field `port`".to_string()));
+		assert_eq!(from_str::<Server>("(slurp \"/etc/passwd\")"), Err("unknown symbol `slurp`".to_string()));
+		assert!(from_str::<Server>("(import math)").is_err());
+		assert_eq!(from_str::<Server>("(echo 1)"), Err("unknown symbol `echo`".to_string()));
+		assert_eq!(from_str::<Server>("(def x 1)\n{ host: \"a\"\n  port: }"), Err("3:9: unexpected token".to_string()));
+
+		let path = env::temp_dir().join(format!("lial-config-{}.lial", ::std::process::id()));
+		fs::write(&path, "{ host: \"b\" port: 2 }\n(def x \"abc)").unwrap();
+		let err = from_file::<Server, _>(&path).unwrap_err();
+		assert_eq!(err, format!("{}:2:8: unexpected end of string", path.canonicalize().unwrap().display()));
+		fs::remove_file(&path).unwrap();
+	}
+}
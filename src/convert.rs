@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use im_rc::Vector;
+
+use interpreter::Value;
+use collections::Map;
+
+impl From<i64> for Value {
+	fn from(value: i64) -> Value {
+		Value::Int(value)
+	}
+}
+
+impl From<i32> for Value {
+	fn from(value: i32) -> Value {
+		Value::Int(value as i64)
+	}
+}
+
+impl From<f64> for Value {
+	fn from(value: f64) -> Value {
+		Value::Real(value)
+	}
+}
+
+impl From<bool> for Value {
+	fn from(value: bool) -> Value {
+		Value::Bool(value)
+	}
+}
+
+impl From<String> for Value {
+	fn from(value: String) -> Value {
+		Value::Str(value)
+	}
+}
+
+impl<'a> From<&'a str> for Value {
+	fn from(value: &'a str) -> Value {
+		Value::Str(value.to_string())
+	}
+}
+
+impl From<()> for Value {
+	fn from(_: ()) -> Value {
+		Value::Nil
+	}
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+	fn from(value: Option<T>) -> Value {
+		match value {
+			Some(value) => value.into(),
+			None => Value::Nil
+		}
+	}
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+	fn from(values: Vec<T>) -> Value {
+		Value::List(values.into_iter().map(|value| Rc::new(value.into())).collect())
+	}
+}
+
+impl<T: Into<Value>> From<HashMap<String, T>> for Value {
+	fn from(values: HashMap<String, T>) -> Value {
+		Value::Map(values.into_iter().map(|(key, value)| (key, Rc::new(value.into()))).collect())
+	}
+}
+
+fn mismatch(expected: &str, found: &Value) -> String {
+	format!("expected {}, found {}", expected, found.type_name())
+}
+
+impl<'a> TryFrom<&'a Value> for i64 {
+	type Error = String;
+	fn try_from(value: &'a Value) -> Result<i64, String> {
+		match value {
+			&Value::Int(i) => Ok(i),
+			_ => Err(mismatch("int", value))
+		}
+	}
+}
+
+impl<'a> TryFrom<&'a Value> for f64 {
+	type Error = String;
+	fn try_from(value: &'a Value) -> Result<f64, String> {
+		match *value {
+			Value::Real(r) => Ok(r),
+			Value::Int(i) => Ok(i as f64),
+			_ => Err(mismatch("real", value))
+		}
+	}
+}
+
+impl<'a> TryFrom<&'a Value> for bool {
+	type Error = String;
+	fn try_from(value: &'a Value) -> Result<bool, String> {
+		match value {
+			&Value::Bool(b) => Ok(b),
+			_ => Err(mismatch("bool", value))
+		}
+	}
+}
+
+impl<'a> TryFrom<&'a Value> for String {
+	type Error = String;
+	fn try_from(value: &'a Value) -> Result<String, String> {
+		match value {
+			Value::Str(s) => Ok(s.clone()),
+			_ => Err(mismatch("string", value))
+		}
+	}
+}
+
+impl<'a> TryFrom<&'a Value> for Vector<Rc<Value>> {
+	type Error = String;
+	fn try_from(value: &'a Value) -> Result<Vector<Rc<Value>>, String> {
+		match value {
+			Value::List(list) => Ok(list.clone()),
+			_ => Err(mismatch("list", value))
+		}
+	}
+}
+
+impl<'a> TryFrom<&'a Value> for HashMap<String, Rc<Value>> {
+	type Error = String;
+	fn try_from(value: &'a Value) -> Result<HashMap<String, Rc<Value>>, String> {
+		match value {
+			Value::Map(map) => Ok(map.iter().map(|(key, value)| (key.clone(), value.clone())).collect()),
+			_ => Err(mismatch("map", value))
+		}
+	}
+}
+
+impl<'a> TryFrom<&'a Value> for Map<String, Rc<Value>> {
+	type Error = String;
+	fn try_from(value: &'a Value) -> Result<Map<String, Rc<Value>>, String> {
+		match value {
+			Value::Map(map) => Ok(map.clone()),
+			_ => Err(mismatch("map", value))
+		}
+	}
+}
+
+macro_rules! try_from_owned(
+	($($t:ty),*) => { $(
+		impl TryFrom<Value> for $t {
+			type Error = String;
+			fn try_from(value: Value) -> Result<$t, String> {
+				<$t>::try_from(&value)
+			}
+		}
+	)* }
+);
+
+try_from_owned!(i64, f64, bool, String, Vector<Rc<Value>>, HashMap<String, Rc<Value>>, Map<String, Rc<Value>>);
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn conversions() {
+		assert_eq!(Value::from(42), Value::Int(42));
+		assert_eq!(Value::from("abc"), Value::Str("abc".to_string()));
+		assert_eq!(Value::from(None::<i64>), Value::Nil);
+		assert_eq!(Value::from(vec![1, 2]), Value::List(vec![Rc::new(Value::Int(1)), Rc::new(Value::Int(2))].into_iter().collect()));
+
+		assert_eq!(i64::try_from(Value::Int(42)), Ok(42));
+		assert_eq!(f64::try_from(&Value::Int(1)), Ok(1.0));
+		assert_eq!(String::try_from(Value::from("abc")), Ok("abc".to_string()));
+		assert_eq!(bool::try_from(Value::Int(1)), Err("expected bool, found int".to_string()));
+	}
+}
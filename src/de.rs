@@ -0,0 +1,292 @@
+use std::fmt;
+use std::vec;
+use std::rc::Rc;
+
+use im_rc::Vector;
+
+use serde::de::{self, Deserialize, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+
+use interpreter::Value;
+use collections::{self, Map};
+pub use ser::Error;
+
+/// Converts a `Value` into any deserializable Rust type. Keywords and
+/// strings both deserialize into strings and unit enum variants, nil into
+/// `None` and `()`.
+pub fn from_value<T: DeserializeOwned>(value: &Value) -> Result<T, Error> {
+	T::deserialize(value)
+}
+
+fn unexpected(value: &Value) -> de::Unexpected<'_> {
+	match value {
+		Value::Str(s) => de::Unexpected::Str(s),
+		&Value::Int(i) => de::Unexpected::Signed(i),
+		&Value::Real(r) => de::Unexpected::Float(r),
+		&Value::Bool(b) => de::Unexpected::Bool(b),
+		&Value::Nil => de::Unexpected::Unit,
+		&Value::List(_) | &Value::Set(_) => de::Unexpected::Seq,
+		&Value::Map(_) => de::Unexpected::Map,
+		_ => de::Unexpected::Other(value.type_name())
+	}
+}
+
+impl<'de> de::Deserializer<'de> for &'de Value {
+	type Error = Error;
+
+	fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		match self {
+			Value::Str(s) => visitor.visit_borrowed_str(s),
+			&Value::Int(i) => visitor.visit_i64(i),
+			&Value::Real(r) => visitor.visit_f64(r),
+			&Value::Bool(b) => visitor.visit_bool(b),
+			&Value::Nil => visitor.visit_unit(),
+			Value::Keyword(name) => visitor.visit_borrowed_str(name),
+			Value::List(list) => visitor.visit_seq(SeqAccess { iter: list.iter() }),
+			Value::Set(set) => visitor.visit_seq(SeqAccess { iter: collections::sorted_set(set).into_iter() }),
+			Value::Map(map) => visitor.visit_map(MapAccess { iter: collections::sorted(map).into_iter(), value: None }),
+			_ => Err(de::Error::custom(format!("cannot deserialize a value of type {}", self.type_name())))
+		}
+	}
+
+	fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		match self {
+			&Value::Nil => visitor.visit_none(),
+			_ => visitor.visit_some(self)
+		}
+	}
+
+	fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_newtype_struct(self)
+	}
+
+	// `:red` or "red" for unit variants, `{ rgb: { 1 2 3 } }` for the others
+	fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+		match self {
+			&Value::Str(ref variant) | &Value::Keyword(ref variant) => {
+				visitor.visit_enum(variant.as_str().into_deserializer())
+			},
+			Value::Map(map) if map.len() == 1 => {
+				let (variant, value) = map.iter().next().unwrap();
+				visitor.visit_enum(EnumAccess { variant, value })
+			},
+			_ => Err(de::Error::invalid_type(unexpected(self), &"an enum variant"))
+		}
+	}
+
+	forward_to_deserialize_any! {
+		bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+		bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+		identifier ignored_any
+	}
+}
+
+// the items of a list or set
+struct SeqAccess<I> {
+	iter: I
+}
+
+impl<'de, I: ExactSizeIterator<Item=&'de Rc<Value>>> de::SeqAccess<'de> for SeqAccess<I> {
+	type Error = Error;
+
+	fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+		match self.iter.next() {
+			Some(value) => seed.deserialize(&**value).map(Some),
+			None => Ok(None)
+		}
+	}
+
+	fn size_hint(&self) -> Option<usize> {
+		Some(self.iter.len())
+	}
+}
+
+struct MapAccess<'de> {
+	iter: vec::IntoIter<(&'de String, &'de Rc<Value>)>,
+	value: Option<&'de Value>
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'de> {
+	type Error = Error;
+
+	fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+		match self.iter.next() {
+			Some((key, value)) => {
+				self.value = Some(value);
+				seed.deserialize(key.as_str().into_deserializer()).map(Some)
+			},
+			None => Ok(None)
+		}
+	}
+
+	fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+		let value = self.value.take().expect("next_value_seed called before next_key_seed");
+		seed.deserialize(value)
+	}
+
+	fn size_hint(&self) -> Option<usize> {
+		Some(self.iter.len())
+	}
+}
+
+struct EnumAccess<'de> {
+	variant: &'de String,
+	value: &'de Value
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+	type Error = Error;
+	type Variant = &'de Value;
+
+	fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, &'de Value), Error> {
+		let variant = seed.deserialize(self.variant.as_str().into_deserializer())?;
+		Ok((variant, self.value))
+	}
+}
+
+impl<'de> de::VariantAccess<'de> for &'de Value {
+	type Error = Error;
+
+	fn unit_variant(self) -> Result<(), Error> {
+		match self {
+			&Value::Nil => Ok(()),
+			_ => Err(de::Error::invalid_type(unexpected(self), &"unit variant"))
+		}
+	}
+
+	fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+		seed.deserialize(self)
+	}
+
+	fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+		de::Deserializer::deserialize_seq(self, visitor)
+	}
+
+	fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+		de::Deserializer::deserialize_map(self, visitor)
+	}
+}
+
+impl<'de> Deserialize<'de> for Value {
+	fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+		deserializer.deserialize_any(ValueVisitor)
+	}
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+	type Value = Value;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("a lial value")
+	}
+
+	fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> { Ok(Value::Bool(v)) }
+	fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> { Ok(Value::Int(v)) }
+
+	// numbers too large for an int become reals, like in JSON parsers of
+	// other languages
+	fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+		if v > i64::MAX as u64 {
+			return Ok(Value::Real(v as f64));
+		}
+		Ok(Value::Int(v as i64))
+	}
+
+	fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> { Ok(Value::Real(v)) }
+	fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> { Ok(Value::Str(v.to_string())) }
+	fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> { Ok(Value::Str(v)) }
+	fn visit_unit<E: de::Error>(self) -> Result<Value, E> { Ok(Value::Nil) }
+	fn visit_none<E: de::Error>(self) -> Result<Value, E> { Ok(Value::Nil) }
+
+	fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+		Value::deserialize(deserializer)
+	}
+
+	fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+		let mut list: Vector<Rc<Value>> = Vector::new();
+		while let Some(item) = seq.next_element::<Value>()? {
+			list.push_back(Rc::new(item));
+		}
+		Ok(Value::List(list))
+	}
+
+	fn visit_map<A: de::MapAccess<'de>>(self, mut entries: A) -> Result<Value, A::Error> {
+		let mut map: Map<String, Rc<Value>> = Map::default();
+		while let Some((key, value)) = entries.next_entry::<String, Value>()? {
+			map.insert(key, Rc::new(value));
+		}
+		Ok(Value::Map(map))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ser::to_value;
+	use testing::eval;
+
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	enum Color {
+		Red,
+		Rgb(u8, u8, u8)
+	}
+
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	struct Config {
+		name: String,
+		port: u16,
+		ratio: f64,
+		tags: Vec<String>,
+		color: Color,
+		parent: Option<Box<Config>>
+	}
+
+	#[test]
+	fn from_lial() {
+		let value = eval("{ name: \"web\" port: 8080 ratio: 0.5 tags: { \"a\" \"b\" } color: :Red parent: nil }").unwrap();
+		let config: Config = from_value(&value).unwrap();
+		assert_eq!(config, Config {
+			name: "web".to_string(),
+			port: 8080,
+			ratio: 0.5,
+			tags: vec!["a".to_string(), "b".to_string()],
+			color: Color::Red,
+			parent: None
+		});
+
+		let value = eval("{ name: \"web\" port: \"8080\" }").unwrap();
+		let err = from_value::<Config>(&value).unwrap_err().to_string();
+		assert_eq!(err, "invalid type: string \"8080\", expected u16");
+		let value = eval("{ name: \"web\" }").unwrap();
+		assert_eq!(from_value::<Config>(&value).unwrap_err().to_string(), "missing field `port`");
+		assert!(from_value::<i64>(&eval("inc").unwrap()).is_err());
+	}
+
+	#[test]
+	fn roundtrip() {
+		let config = Config {
+			name: "db".to_string(),
+			port: 5432,
+			ratio: 1.0,
+			tags: vec![],
+			color: Color::Rgb(1, 2, 3),
+			parent: Some(Box::new(Config {
+				name: "root".to_string(),
+				port: 1,
+				ratio: 0.0,
+				tags: vec!["x".to_string()],
+				color: Color::Red,
+				parent: None
+			}))
+		};
+		let value = to_value(&config).unwrap();
+		assert_eq!(from_value::<Config>(&value).unwrap(), config);
+		assert_eq!(to_value(&Color::Red), Ok(Value::Keyword("Red".to_string())));
+
+		// keywords come back as strings, everything else is unchanged
+		let value = eval("{ a: { 1 2.5 \"x\" nil } b: { c: true } }").unwrap();
+		assert_eq!(from_value::<Value>(&value).unwrap(), *value);
+		assert_eq!(from_value::<Value>(&eval(":k").unwrap()).unwrap(), Value::Str("k".to_string()));
+	}
+}
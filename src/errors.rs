@@ -0,0 +1,153 @@
+use std::fmt;
+use std::rc::Rc;
+
+use interpreter::{Value, EvalResult, Builtins};
+use collections::Map;
+
+// A thrown value travels with its error, so that `try` gets the value back;
+// any other error (of a builtin, the evaluator, ...) is caught as
+// `{ type: :error message: "..." }`.
+pub const BUILTINS: &Builtins = &[
+	("throw", throw, "(throw x) Throws x (typically a map with type and message) to the nearest try that catches it.")
+];
+
+/// An error of the evaluation: its message and, if it was raised by `throw`,
+/// the thrown value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+	// boxed to keep results small, the tree-walker has many of them on the
+	// stack
+	inner: Box<(String, Option<Rc<Value>>)>
+}
+
+impl Error {
+	pub fn message(&self) -> &str {
+		&self.inner.0
+	}
+
+	/// The thrown value, `None` for errors that were not raised by `throw`.
+	pub fn value(&self) -> Option<&Rc<Value>> {
+		self.inner.1.as_ref()
+	}
+}
+
+impl From<String> for Error {
+	fn from(message: String) -> Error {
+		Error { inner: Box::new((message, None)) }
+	}
+}
+
+impl<'a> From<&'a str> for Error {
+	fn from(message: &'a str) -> Error {
+		Error::from(message.to_string())
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.message())
+	}
+}
+
+pub fn throw(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 1 {
+		return Err("throw takes only one argument".into());
+	}
+
+	Err(raise(args[0].clone()))
+}
+
+// The error that throws value, its message is the message of a map or the
+// printed value.
+pub fn raise(value: Rc<Value>) -> Error {
+	let message = match *value {
+		Value::Map(ref map) => match map.get("message").map(|message| &**message) {
+			Some(Value::Str(message)) => message.clone(),
+			Some(message) => message.to_string(),
+			None => value.to_string()
+		},
+		_ => value.to_string()
+	};
+	Error { inner: Box::new((message, Some(value))) }
+}
+
+// The value of a caught error.
+pub fn caught(error: Error) -> Rc<Value> {
+	let (message, value) = *error.inner;
+	if let Some(value) = value {
+		return value;
+	}
+
+	let mut map: Map<String, Rc<Value>> = Map::default();
+	map.insert("type".to_string(), Rc::new(Value::Keyword("error".to_string())));
+	map.insert("message".to_string(), Rc::new(Value::Str(message)));
+	Rc::new(Value::Map(map))
+}
+
+// Whether a catch clause with the keyword `kind` catches error: maps by their
+// `type`, other values by the name of their type, `:default` everything.
+pub fn is_kind(kind: &str, error: &Value) -> bool {
+	if kind == "default" {
+		return true;
+	}
+	match *error {
+		Value::Map(ref map) => match map.get("type").map(|kind| &**kind) {
+			Some(&Value::Keyword(ref name)) | Some(&Value::Str(ref name)) => name == kind,
+			_ => false
+		},
+		_ => error.type_name() == kind
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use testing::{eval, for_each_backend, ok};
+
+	#[test]
+	fn try_catch() {
+		for_each_backend(|eval| {
+			// any value can be thrown, maps are matched by their type
+			assert_eq!(eval("(try (throw { type: :not-found message: \"no user 3\" }) (catch :invalid e 1) (catch :not-found e (get e :message)))"), ok("no user 3"));
+			assert_eq!(eval("(try (throw 42) (catch :int e (+ e 1)))"), ok("43"));
+			assert_eq!(eval("(try (throw :oops) (catch (fn {e} (= e :oops)) e { :caught e }))"), ok("{ :caught :oops }"));
+			assert_eq!(eval("(try (+ 1 2) (catch :default e 0))"), ok("3"));
+
+			// errors of builtins and the evaluator are caught as maps of type :error
+			assert_eq!(eval("(try (+ 1 :a) (catch :error e e))"), ok("{ message: `+` takes arguments of type int or real type: :error }"));
+			assert_eq!(eval("(defn f {x} x) (try (f) (catch :default e (get e :message)))"), ok("`f` takes 1 argument, got 0"));
+
+			// uncaught errors propagate with their message
+			assert_eq!(eval("(try (throw { type: :a message: \"failed\" }) (catch :b e 1))"), Err("failed".to_string()));
+			assert_eq!(eval("(try (throw { type: :a }) (catch :b e 1))"), Err("{ type: :a }".to_string()));
+			assert_eq!(eval("(try (try (throw { type: :a }) (catch :b e 1)) (catch :a e (get e :type)))"), ok(":a"));
+			assert_eq!(eval("(try (throw 1) (catch :int e (throw { message: \"again\" })))"), Err("again".to_string()));
+
+			// finally runs in any case
+			assert_eq!(eval("(def log (atom {})) (defn note {x} (swap! log (fn {l} (conj l x))))
+				(try (note 1) (finally (note :a)))
+				(try (try (throw 2) (finally (note :b))) (catch :int e (note e)))
+				(try (throw 3) (catch :int e (note e)) (finally (note :c)))
+				@log"), ok("{ 1 :a :b 2 3 :c }"));
+			assert_eq!(eval("(try 1 (finally (throw { message: \"cleanup\" })))"), Err("cleanup".to_string()));
+
+			// an uncaught value is not mistaken for a later error with the same message
+			assert!(eval("(throw { type: :old message: \"`+` takes arguments of type int or real\" })").is_err());
+			assert_eq!(eval("(try (+ 1 :a) (catch :default e (get e :type)))"), ok(":error"));
+			// nor lost by an error that is caught while it propagates
+			assert_eq!(eval("(try (try (throw { type: :a }) (finally (try (+ 1 :x) (catch :error e 0)))) (catch :a e (get e :type)))"), ok(":a"));
+
+			// handlers see the variables around them, def stays allowed at the top level
+			assert_eq!(eval("(defn safe-div {a b} (try (throw a) (catch :int e (* e b)))) (safe-div 2 3)"), ok("6"));
+			assert_eq!(eval("(try (def defined 1)) defined"), ok("1"));
+			assert_eq!(eval("(defn g {} (try (def x 1)))"), Err("cannot def `x` inside of a function, use set! to assign to a variable".to_string()));
+			assert_eq!(eval("(try 1 (finally 2) (catch :a e 3))"), Err("illegal try syntax".to_string()));
+			assert_eq!(eval("(try (throw 1) (catch 1 e 3))"), Err("catch takes a keyword or a predicate, got int".to_string()));
+		});
+
+		// uncaught errors carry the thrown value
+		let error = eval("(throw { type: :a message: \"failed\" })").unwrap_err();
+		assert_eq!(error.message(), "failed");
+		assert_eq!(error.value().map(|value| value.to_string()), Some("{ message: failed type: :a }".to_string()));
+		assert_eq!(eval("(+ 1 :a)").unwrap_err().value(), None);
+	}
+}
@@ -0,0 +1,213 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use im_rc::Vector;
+
+use interpreter::{Value, EvalResult, Builtins};
+use errors::Error;
+
+pub const BUILTINS: &Builtins = &[
+	("slurp", slurp, "(slurp path) Reads the whole file into a string."),
+	("spit", spit, "(spit path x) Writes x to the file, replacing its content."),
+	("append-file", append_file, "(append-file path x) Appends x to the file, creating it if needed."),
+	("read-lines", read_lines, "(read-lines path) Reads the file into a list of lines."),
+	("file-exists?", file_exists, "(file-exists? path) Whether the file or directory exists."),
+	("dir?", is_dir, "(dir? path) Whether path is a directory."),
+	("list-dir", list_dir, "(list-dir path) Sorted list of the names of all entries of the directory."),
+	("mkdir", mkdir, "(mkdir path) Creates the directory and all missing parents."),
+	("delete-file", delete_file, "(delete-file path) Deletes the file or empty directory."),
+	("path/join", path_join, "(path/join part ...) Joins the parts of a path."),
+	("path/basename", path_basename, "(path/basename path) The last component of the path."),
+	("path/dirname", path_dirname, "(path/dirname path) The path without its last component."),
+	("path/extension", path_extension, "(path/extension path) The extension of the file name or nil."),
+	("path/absolute", path_absolute, "(path/absolute path) The canonical, absolute form of an existing path.")
+];
+
+fn io_error(func: &str, path: &str, e: io::Error) -> Error {
+	format!("{}: {}: {}", func, path, e).into()
+}
+
+fn string_args<'a>(func: &str, args: &'a [Rc<Value>], n: usize) -> Result<Vec<&'a String>, String> {
+	if args.len() != n {
+		return Err(format!("{} takes {} argument(s)", func, n));
+	}
+
+	let mut strings: Vec<&String> = vec![];
+	for arg in args {
+		match **arg {
+			Value::Str(ref string) => strings.push(string),
+			_ => { return Err(format!("{} only takes strings as arguments", func)); }
+		}
+	}
+	Ok(strings)
+}
+
+fn path_to_value(path: &Path) -> EvalResult {
+	match path.to_str() {
+		Some(path) => Ok(Rc::new(Value::Str(path.to_string()))),
+		None => Err(format!("{}: path is not valid unicode", path.display()).into())
+	}
+}
+
+pub fn slurp(args: Vec<Rc<Value>>) -> EvalResult {
+	let path = string_args("slurp", &args, 1)?[0];
+	match fs::read_to_string(path) {
+		Ok(content) => Ok(Rc::new(Value::Str(content))),
+		Err(e) => Err(io_error("slurp", path, e))
+	}
+}
+
+pub fn spit(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 2 {
+		return Err("spit takes a path and the content to write".into());
+	}
+	let path = string_args("spit", &args[..1], 1)?[0];
+	match fs::write(path, args[1].to_string()) {
+		Ok(_) => Ok(Rc::new(Value::Nil)),
+		Err(e) => Err(io_error("spit", path, e))
+	}
+}
+
+pub fn append_file(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 2 {
+		return Err("append-file takes a path and the content to append".into());
+	}
+	let path = string_args("append-file", &args[..1], 1)?[0];
+	let res = OpenOptions::new().create(true).append(true).open(path)
+		.and_then(|mut file| file.write_all(args[1].to_string().as_bytes()));
+	match res {
+		Ok(_) => Ok(Rc::new(Value::Nil)),
+		Err(e) => Err(io_error("append-file", path, e))
+	}
+}
+
+pub fn read_lines(args: Vec<Rc<Value>>) -> EvalResult {
+	let path = string_args("read-lines", &args, 1)?[0];
+	match fs::read_to_string(path) {
+		Ok(content) => {
+			let lines: Vector<Rc<Value>> = content.lines()
+				.map(|line| Rc::new(Value::Str(line.to_string()))).collect();
+			Ok(Rc::new(Value::List(lines)))
+		},
+		Err(e) => Err(io_error("read-lines", path, e))
+	}
+}
+
+pub fn file_exists(args: Vec<Rc<Value>>) -> EvalResult {
+	let path = string_args("file-exists?", &args, 1)?[0];
+	Ok(Rc::new(Value::Bool(Path::new(path).exists())))
+}
+
+pub fn is_dir(args: Vec<Rc<Value>>) -> EvalResult {
+	let path = string_args("dir?", &args, 1)?[0];
+	Ok(Rc::new(Value::Bool(Path::new(path).is_dir())))
+}
+
+pub fn list_dir(args: Vec<Rc<Value>>) -> EvalResult {
+	let path = string_args("list-dir", &args, 1)?[0];
+	let entries = fs::read_dir(path)
+		.and_then(|entries| entries.map(|entry| entry.map(|entry| entry.file_name())).collect::<io::Result<Vec<_>>>());
+	let mut names: Vec<String> = match entries {
+		Ok(entries) => entries.into_iter().map(|name| name.to_string_lossy().into_owned()).collect(),
+		Err(e) => { return Err(io_error("list-dir", path, e)); }
+	};
+	names.sort();
+	Ok(Rc::new(Value::List(names.into_iter().map(|name| Rc::new(Value::Str(name))).collect())))
+}
+
+pub fn mkdir(args: Vec<Rc<Value>>) -> EvalResult {
+	let path = string_args("mkdir", &args, 1)?[0];
+	match fs::create_dir_all(path) {
+		Ok(_) => Ok(Rc::new(Value::Nil)),
+		Err(e) => Err(io_error("mkdir", path, e))
+	}
+}
+
+pub fn delete_file(args: Vec<Rc<Value>>) -> EvalResult {
+	let path = string_args("delete-file", &args, 1)?[0];
+	let res = if Path::new(path).is_dir() {
+		fs::remove_dir(path)
+	} else {
+		fs::remove_file(path)
+	};
+	match res {
+		Ok(_) => Ok(Rc::new(Value::Nil)),
+		Err(e) => Err(io_error("delete-file", path, e))
+	}
+}
+
+pub fn path_join(args: Vec<Rc<Value>>) -> EvalResult {
+	let mut path = PathBuf::new();
+	for part in string_args("path/join", &args, args.len())? {
+		path.push(part);
+	}
+	path_to_value(&path)
+}
+
+pub fn path_basename(args: Vec<Rc<Value>>) -> EvalResult {
+	let path = string_args("path/basename", &args, 1)?[0];
+	match Path::new(path).file_name() {
+		Some(name) => path_to_value(Path::new(name)),
+		None => Ok(Rc::new(Value::Nil))
+	}
+}
+
+pub fn path_dirname(args: Vec<Rc<Value>>) -> EvalResult {
+	let path = string_args("path/dirname", &args, 1)?[0];
+	match Path::new(path).parent() {
+		Some(dir) => path_to_value(dir),
+		None => Ok(Rc::new(Value::Nil))
+	}
+}
+
+pub fn path_extension(args: Vec<Rc<Value>>) -> EvalResult {
+	let path = string_args("path/extension", &args, 1)?[0];
+	match Path::new(path).extension() {
+		Some(ext) => path_to_value(Path::new(ext)),
+		None => Ok(Rc::new(Value::Nil))
+	}
+}
+
+pub fn path_absolute(args: Vec<Rc<Value>>) -> EvalResult {
+	let path = string_args("path/absolute", &args, 1)?[0];
+	match fs::canonicalize(path) {
+		Ok(path) => path_to_value(&path),
+		Err(e) => Err(io_error("path/absolute", path, e))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+	use testing::string;
+
+	#[test]
+	fn files() {
+		let dir = env::temp_dir().join(format!("lial-files-{}", ::std::process::id()));
+		let dir = dir.to_str().unwrap();
+		let file = path_join(vec![string(dir), string("a"), string("b.txt")]).unwrap();
+		let parent = path_dirname(vec![file.clone()]).unwrap();
+		assert_eq!(path_basename(vec![file.clone()]), Ok(string("b.txt")));
+		assert_eq!(path_extension(vec![file.clone()]), Ok(string("txt")));
+
+		assert!(spit(vec![file.clone(), string("x")]).is_err());
+		mkdir(vec![parent.clone()]).unwrap();
+		spit(vec![file.clone(), string("1\n")]).unwrap();
+		append_file(vec![file.clone(), Rc::new(Value::Int(2))]).unwrap();
+		assert_eq!(slurp(vec![file.clone()]), Ok(string("1\n2")));
+		assert_eq!(read_lines(vec![file.clone()]),
+			Ok(Rc::new(Value::List(vec![string("1"), string("2")].into_iter().collect()))));
+		assert_eq!(list_dir(vec![parent.clone()]),
+			Ok(Rc::new(Value::List(vec![string("b.txt")].into_iter().collect()))));
+		assert_eq!(file_exists(vec![file.clone()]), Ok(Rc::new(Value::Bool(true))));
+
+		delete_file(vec![file.clone()]).unwrap();
+		assert_eq!(file_exists(vec![file.clone()]), Ok(Rc::new(Value::Bool(false))));
+		assert!(slurp(vec![file.clone()]).unwrap_err().message().starts_with("slurp: "));
+		delete_file(vec![parent]).unwrap();
+		delete_file(vec![string(dir)]).unwrap();
+	}
+}
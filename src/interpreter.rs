@@ -1,9 +1,63 @@
-use std::collections::{LinkedList, HashMap};
+// The interpreter predates clippy, its idioms (explicit returns, ref patterns,
+// len() == 0, ...) are kept.
+#![allow(clippy::inherent_to_string, clippy::len_zero, clippy::match_ref_pats,
+	clippy::needless_borrowed_reference, clippy::needless_return, clippy::new_without_default,
+	clippy::redundant_field_names, clippy::while_let_on_iterator)]
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::rc::Rc;
-use std::collections::linked_list::IntoIter;
+use std::path::Path;
+use std::fs::File;
+use std::io::Read;
+use std::mem;
+
+use im_rc::Vector;
+use im_rc::vector::ConsumingIter as IntoIter;
 
-use parser::Node;
+use parser::{self, Node};
 use builtins;
+use files;
+use system;
+use lists;
+use maps;
+use sets;
+use atoms;
+use errors::{self, Error};
+use json;
+use macros::{self, Clause, Macro};
+use modules::{self, Modules};
+use vm::{self, Closure};
+use analyzer;
+use params::{self, Params};
+use collections::{self, Map, Set};
+use symbol::{self, Symbol};
+
+const PRELUDE: &str = include_str!("prelude.lial");
+
+thread_local! {
+	// The atoms whose values are being printed: an atom can contain itself,
+	// inside of its own value it is printed without the value.
+	static PRINTING_ATOMS: RefCell<HashSet<*const RefCell<Rc<Value>>>> = RefCell::new(HashSet::new());
+}
+
+pub(crate) const SPECIAL_FORMS: &[(&str, &str)] = &[
+	("fn", "(fn {a (b default) & more} body...) (fn ({params} body...) ...) Creates a function (with an arity per clause), an optional leading string in body is its doc."),
+	("def", "(def name x) Defines name globally (or in the namespace of the module), not allowed inside of fn."),
+	("set!", "(set! name x) Assigns x to the nearest parameter or global name, errors if there is none."),
+	("defmacro", "(defmacro name {params} template) Defines a macro, `& rest` in params and template splices."),
+	("do", "(do body...) Evaluates all expressions and returns the last."),
+	("if", "(if test then else) Evaluates then if test is true, else otherwise."),
+	("and", "(and x ...) true if all arguments are true, stops at the first false one."),
+	("or", "(or x ...) true if any argument is true, stops at the first true one."),
+	("import", "(import name) (import \"file.lial\" :as alias :only {names}) Loads a module."),
+	("let", "(let {pattern x ...} body...) Binds each x to the variables of its pattern (a name, a list or a map pattern) in the bindings after it and in body."),
+	("match", "(match x pattern body pattern :when guard body ...) Evaluates the body of the first pattern that matches x (and whose guard is true) with its variables bound, errors if none does."),
+	("apply", "(apply f x ... xs) Calls f with the arguments x ... followed by the items of the list xs."),
+	("try", "(try body... (catch kind e handler...) (finally cleanup...)) Evaluates body, errors are handled by the first catch whose keyword or predicate matches, cleanup always runs.")
+];
 
 #[derive(Debug, Clone)]
 pub enum Value {
@@ -12,10 +66,80 @@ pub enum Value {
 	Real(f64),
 	Bool(bool),
 	Nil,
-	List(LinkedList<Rc<Value>>),
-	Map(HashMap<String, Rc<Value>>),
-	Func(Vec<String>, Vec<Rc<Node>>),
-	ExternalFn(fn(Vec<Rc<Value>>) -> EvalResult)
+	Keyword(String),
+	List(Vector<Rc<Value>>),
+	Map(Map<String, Rc<Value>>),
+	Set(Set<Rc<Value>>),
+	// the only mutable value, shared by all copies (see `atoms`)
+	Atom(Rc<RefCell<Rc<Value>>>),
+	Func(Lambda),
+	Macro(Macro),
+	ExternalFn(Builtin)
+}
+
+// How top level expressions are evaluated: by walking the tree of nodes or
+// by compiling them to bytecode for the `vm`. Functions of both backends can
+// call each other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+	TreeWalker,
+	Vm
+}
+
+#[derive(Debug, Clone)]
+pub struct Lambda {
+	// the name it was defined as, for errors
+	name: Option<Symbol>,
+	// the first arity that accepts the arguments is called
+	arities: Vec<Arity>,
+	// namespace the function was defined in
+	pub(crate) ns: Option<Rc<String>>,
+	// frame of the function the function was defined in
+	closure: Option<Rc<Frame>>,
+	doc: Option<String>
+}
+
+#[derive(Debug, Clone)]
+pub struct Arity {
+	params: Params,
+	body: Vec<Rc<Node>>,
+	// bytecode and captured values of functions created by the vm
+	pub(crate) code: Option<Rc<Closure>>
+}
+
+impl Lambda {
+	pub(crate) fn compiled(closures: Vec<Rc<Closure>>, ns: Option<Rc<String>>) -> Lambda {
+		Lambda {
+			name: None,
+			doc: closures[0].proto.doc.clone(),
+			arities: closures.into_iter().map(|code| Arity {
+				params: code.proto.params.clone(),
+				body: code.proto.body.clone(),
+				code: Some(code)
+			}).collect(),
+			ns: ns,
+			closure: None
+		}
+	}
+
+	pub(crate) fn arity(&self, argc: usize) -> Result<&Arity, String> {
+		match self.arities.iter().find(|arity| arity.params.accepts(argc)) {
+			Some(arity) => Ok(arity),
+			None => Err(params::arity_error(self.name, self.arities.iter().map(|arity| &arity.params), argc))
+		}
+	}
+}
+
+// value, with its name set if it is an unnamed function
+fn named(name: Symbol, value: Rc<Value>) -> Rc<Value> {
+	match *value {
+		Value::Func(ref lambda) if lambda.name.is_none() => {
+			let mut lambda = lambda.clone();
+			lambda.name = Some(name);
+			Rc::new(Value::Func(lambda))
+		},
+		_ => value.clone()
+	}
 }
 
 impl PartialEq for Value {
@@ -26,13 +150,53 @@ impl PartialEq for Value {
 			(&Value::Real(a), &Value::Real(b)) => a == b,
 			(&Value::Bool(a), &Value::Bool(b)) => a == b,
 			(&Value::Nil, &Value::Nil) => true,
+			(&Value::Keyword(ref a), &Value::Keyword(ref b)) => a == b,
 			(&Value::List(ref a), &Value::List(ref b)) => a == b,
 			(&Value::Map(ref a), &Value::Map(ref b)) => a == b,
+			(&Value::Set(ref a), &Value::Set(ref b)) => a == b,
+			(&Value::Atom(ref a), &Value::Atom(ref b)) => Rc::ptr_eq(a, b),
 			_ => false
 		}
 	}
 }
 
+// Values are hashed to be members of sets. Functions, macros and builtins
+// are not equal to anything, they only hash their type. Atoms are only
+// equal to themselves and hash their address.
+impl Eq for Value {}
+
+impl Hash for Value {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.type_name().hash(state);
+		match self {
+			&Value::Str(ref s) | &Value::Keyword(ref s) => s.hash(state),
+			&Value::Int(i) => i.hash(state),
+			// 0.0 and -0.0 are equal
+			&Value::Real(r) => (if r == 0.0 { 0.0f64 } else { r }).to_bits().hash(state),
+			&Value::Bool(b) => b.hash(state),
+			&Value::List(ref list) => list.hash(state),
+			&Value::Map(ref map) => {
+				for (key, value) in collections::sorted(map) {
+					key.hash(state);
+					value.hash(state);
+				}
+			},
+			&Value::Set(ref set) => {
+				// independent of the order of the items
+				let mut sum: u64 = 0;
+				for item in set {
+					let mut hasher = DefaultHasher::new();
+					item.hash(&mut hasher);
+					sum = sum.wrapping_add(hasher.finish());
+				}
+				sum.hash(state);
+			},
+			&Value::Atom(ref cell) => (&**cell as *const RefCell<Rc<Value>>).hash(state),
+			_ => {}
+		}
+	}
+}
+
 impl Value {
 	pub fn to_bool(&self) -> bool {
 		match self {
@@ -40,10 +204,29 @@ impl Value {
 			&Value::Nil => false,
 			&Value::Int(i) => i != 0,
 			&Value::Real(r) => r != 0.0,
+			&Value::Keyword(_) => true,
 			_ => false
 		}
 	}
 
+	pub fn type_name(&self) -> &'static str {
+		match self {
+			&Value::Str(_) => "string",
+			&Value::Int(_) => "int",
+			&Value::Real(_) => "real",
+			&Value::Bool(_) => "bool",
+			&Value::Nil => "nil",
+			&Value::Keyword(_) => "keyword",
+			&Value::List(_) => "list",
+			&Value::Map(_) => "map",
+			&Value::Set(_) => "set",
+			&Value::Atom(_) => "atom",
+			&Value::Func(_) => "fn",
+			&Value::Macro(_) => "macro",
+			&Value::ExternalFn(_) => "builtin"
+		}
+	}
+
 	pub fn to_string(&self) -> String {
 		match self {
 			&Value::Str(ref value) => value.clone(),
@@ -51,6 +234,7 @@ impl Value {
 			&Value::Real(value) => value.to_string(),
 			&Value::Bool(value) => value.to_string(),
 			&Value::Nil => "<Nil>".to_string(),
+			&Value::Keyword(ref name) => format!(":{}", name),
 			&Value::List(ref list) => {
 				let mut string = "{ ".to_string();
 				for item in list {
@@ -65,7 +249,7 @@ impl Value {
 					return "{:}".to_string();
 				}
 				let mut string = "{ ".to_string();
-				for (key, value) in map {
+				for (key, value) in collections::sorted(map) {
 					string.push_str(key.as_str());
 					string.push_str(": ");
 					string.push_str(value.to_string().as_str());
@@ -74,89 +258,426 @@ impl Value {
 				string.push('}');
 				string
 			},
-			&Value::Func(_, _) => "<Fn::Internal>".to_string(),
+			&Value::Set(ref set) => {
+				let mut string = "#{ ".to_string();
+				for item in collections::sorted_set(set) {
+					string.push_str(item.to_string().as_str());
+					string.push(' ');
+				}
+				string.push('}');
+				string
+			},
+			&Value::Atom(ref cell) => {
+				let ptr = Rc::as_ptr(cell);
+				if !PRINTING_ATOMS.with(|printing| printing.borrow_mut().insert(ptr)) {
+					return "<Atom>".to_string();
+				}
+				let string = format!("<Atom {}>", cell.borrow().to_string());
+				PRINTING_ATOMS.with(|printing| printing.borrow_mut().remove(&ptr));
+				string
+			},
+			&Value::Func(_) => "<Fn::Internal>".to_string(),
+			&Value::Macro(_) => "<Macro>".to_string(),
 			&Value::ExternalFn(_) => "<Fn::External>".to_string()
 		}
 	}
 }
 
-pub type EvalResult = Result<Rc<Value>, String>;
+pub type EvalResult = Result<Rc<Value>, Error>;
+pub type Builtin = fn(Vec<Rc<Value>>) -> EvalResult;
+// name, function and documentation
+pub type Builtins = [(&'static str, Builtin, &'static str)];
 
 
-pub struct Env {
-	size: usize,
-	scope: Vec<HashMap<String, Rc<Value>>>
+// Parameters of a function call. The analyzer resolves local variables to
+// (depth, index): the frame `depth` parents up and the index in its values.
+#[derive(Debug)]
+pub(crate) struct Frame {
+	// shared with the closures created in the call, `set!` assigns to them
+	values: RefCell<Vec<Rc<Value>>>,
+	parent: Option<Rc<Frame>>
+}
+
+// Globals live in slots, the analyzer resolves their names to slot indices.
+// A slot is declared (but empty) between its first use and the `def`.
+pub(crate) struct Env {
+	slots: HashMap<Symbol, usize>,
+	names: Vec<Symbol>,
+	globals: Vec<Option<Rc<Value>>>,
+	// frame of the running function
+	frame: Option<Rc<Frame>>
 }
 impl Env {
-	pub fn new() -> Env {
-		Env { scope: vec![], size: 0 }
+	pub(crate) fn new() -> Env {
+		Env { slots: HashMap::new(), names: vec![], globals: vec![], frame: None }
 	}
 
-	pub fn exit(&mut self) {
-		self.size -= 1;
-		self.scope.pop().expect("");
+	// Enters a function with its arguments and the frame it was defined in,
+	// returns the frame of the caller.
+	pub(crate) fn enter(&mut self, values: Vec<Rc<Value>>, parent: Option<Rc<Frame>>) -> Option<Rc<Frame>> {
+		self.frame.replace(Rc::new(Frame { values: RefCell::new(values), parent: parent }))
 	}
 
-	pub fn enter(&mut self) {
-		self.size += 1;
-		self.scope.push( HashMap::new() );
+	pub(crate) fn exit(&mut self, caller: Option<Rc<Frame>>) {
+		self.frame = caller;
 	}
 
-	pub fn define(&mut self, key: String, value: Rc<Value>) {
-		self.scope[self.size - 1].insert(key, value);
+	pub(crate) fn frame(&self) -> Option<Rc<Frame>> {
+		self.frame.clone()
 	}
 
-	pub fn define_global(&mut self, key: String, value: Rc<Value>) {
-		self.scope[0].insert(key, value);
+	fn frame_at(&self, depth: usize) -> Option<&Rc<Frame>> {
+		let mut frame = self.frame.as_ref();
+		for _ in 0..depth {
+			frame = frame.and_then(|frame| frame.parent.as_ref());
+		}
+		frame
 	}
 
-	pub fn lookup(&self, key: &String) -> Option<Rc<Value>> {
-		let mut i: i32 = self.size as i32 - 1;
-		while i >= 0 {
-			if let Some(value) = self.scope[i as usize].get(key) {
-				return Some(value.clone());
-			}
-			i -= 1;
+	pub(crate) fn local(&self, depth: usize, index: usize) -> Option<Rc<Value>> {
+		self.frame_at(depth).and_then(|frame| frame.values.borrow().get(index).cloned())
+	}
+
+	pub(crate) fn set_local(&mut self, depth: usize, index: usize, value: Rc<Value>) -> Result<(), String> {
+		match self.frame_at(depth).map(|frame| frame.values.borrow_mut()) {
+			Some(ref mut values) if index < values.len() => {
+				values[index] = value;
+				Ok(())
+			},
+			_ => Err("unbound local variable".to_string())
 		}
-		None
+	}
+
+	pub(crate) fn declare(&mut self, key: Symbol) -> usize {
+		if let Some(&slot) = self.slots.get(&key) {
+			return slot;
+		}
+		self.names.push(key);
+		self.globals.push(None);
+		self.slots.insert(key, self.globals.len() - 1);
+		self.globals.len() - 1
+	}
+
+	pub(crate) fn define_global(&mut self, key: Symbol, value: Rc<Value>) {
+		let slot = self.declare(key);
+		self.globals[slot] = Some(value);
+	}
+
+	// Assigns to a defined global, unlike `define_global`.
+	pub(crate) fn set_global(&mut self, slot: usize, value: Rc<Value>) -> Result<(), String> {
+		match self.globals[slot] {
+			Some(_) => {
+				self.globals[slot] = Some(value);
+				Ok(())
+			},
+			None => Err(format!("cannot set! `{}`, it is not defined yet", self.names[slot]))
+		}
+	}
+
+	pub(crate) fn slot(&self, key: Symbol) -> Option<usize> {
+		self.slots.get(&key).cloned()
+	}
+
+	pub(crate) fn global(&self, slot: usize) -> EvalResult {
+		match self.globals[slot] {
+			Some(ref value) => Ok(value.clone()),
+			None => Err(format!("`{}` is not defined yet", self.names[slot]).into())
+		}
+	}
+
+	pub(crate) fn lookup_global(&self, key: Symbol) -> Option<Rc<Value>> {
+		self.slot(key).and_then(|slot| self.globals[slot].clone())
+	}
+
+	// all defined globals
+	pub(crate) fn globals(&self) -> Vec<(Symbol, Rc<Value>)> {
+		self.names.iter().zip(&self.globals)
+			.filter_map(|(&name, value)| value.as_ref().map(|value| (name, value.clone())))
+			.collect()
 	}
 }
 
 pub struct Interpreter {
-	env: Env
+	env: Env,
+	ns: Option<Rc<String>>,
+	modules: Modules,
+	docs: HashMap<String, &'static str>,
+	sandboxed: bool,
+	backend: Backend
 }
 
 impl Interpreter {
 	pub fn new() -> Interpreter {
+		Interpreter::with_backend(Backend::TreeWalker)
+	}
+
+	pub fn with_backend(backend: Backend) -> Interpreter {
+		let mut interpreter = Interpreter::without_prelude();
+		interpreter.backend = backend;
+		interpreter.with_prelude()
+	}
+
+	pub fn without_prelude() -> Interpreter {
+		let mut interpreter = Interpreter::pure();
+		interpreter.expose_builtins(files::BUILTINS);
+		interpreter.expose_builtins(system::BUILTINS);
+		interpreter
+	}
+
+	// An interpreter with the prelude but without any builtins that access
+	// files, stdin/stdout, the environment or the process, `import` is
+	// disabled too. Used to evaluate untrusted code like configuration files.
+	pub fn sandboxed() -> Interpreter {
+		let mut interpreter = Interpreter::pure().with_prelude();
+		interpreter.sandboxed = true;
+		interpreter
+	}
+
+	fn pure() -> Interpreter {
 		let mut interpreter = Interpreter {
-			env: Env::new()
+			env: Env::new(),
+			ns: None,
+			modules: Modules::new(),
+			docs: HashMap::new(),
+			sandboxed: false,
+			backend: Backend::TreeWalker
 		};
-		interpreter.env.enter();
-		interpreter.expose_external_func("+", builtins::add);
-		interpreter.expose_external_func("-", builtins::sub);
-		interpreter.expose_external_func("*", builtins::mul);
-		interpreter.expose_external_func("=", builtins::equals);
-		interpreter.expose_external_func("hex", builtins::hex);
-		interpreter.expose_external_func("bin", builtins::bin);
-		interpreter.expose_external_func("echo", builtins::echo);
+		interpreter.expose_builtins(builtins::BUILTINS);
+		interpreter.expose_builtins(lists::BUILTINS);
+		interpreter.expose_builtins(maps::BUILTINS);
+		interpreter.expose_builtins(sets::BUILTINS);
+		interpreter.expose_builtins(atoms::BUILTINS);
+		interpreter.expose_builtins(errors::BUILTINS);
+		interpreter.expose_builtins(json::BUILTINS);
+		interpreter.eval_str(atoms::SWAP).expect("swap!: evaluation failed");
+		interpreter.define("*args*", Rc::new(Value::List(Vector::new())));
 		interpreter
 	}
 
-	pub fn expose_external_func(&mut self, name: &'static str, func: fn(Vec<Rc<Value>>) -> EvalResult) {
-		self.env.define(name.to_string(), Rc::new( Value::ExternalFn(func) ));
+	fn with_prelude(mut self) -> Interpreter {
+		for node in parser::parse(&PRELUDE.to_string()).expect("prelude: syntax error") {
+			self.eval(Rc::new(node)).expect("prelude: evaluation failed");
+		}
+		self
+	}
+
+	pub fn backend(&self) -> Backend {
+		self.backend
+	}
+
+	// Functions defined before keep running in the backend they were
+	// created by.
+	pub fn set_backend(&mut self, backend: Backend) {
+		self.backend = backend;
+	}
+
+	pub fn expose_external_func(&mut self, name: &'static str, func: Builtin) {
+		self.env.define_global(Symbol::intern(name), Rc::new( Value::ExternalFn(func) ));
+	}
+
+	pub fn expose_builtins(&mut self, builtins: &Builtins) {
+		for &(name, func, doc) in builtins {
+			self.expose_external_func(name, func);
+			self.docs.insert(name.to_string(), doc);
+		}
+	}
+
+	pub fn globals(&self) -> Vec<(String, Rc<Value>)> {
+		let mut globals: Vec<(String, Rc<Value>)> = self.env.globals().into_iter()
+			.map(|(name, value)| (name.to_string(), value))
+			.collect();
+		globals.sort_by(|a, b| a.0.cmp(&b.0));
+		globals
+	}
+
+	// all global names plus the special forms
+	pub fn names(&self) -> Vec<String> {
+		let mut names: Vec<String> = self.env.globals().into_iter().map(|(name, _)| name.to_string()).collect();
+		names.extend(SPECIAL_FORMS.iter().map(|&(name, _)| name.to_string()));
+		names.sort();
+		names.dedup();
+		names
 	}
 
-	pub fn eval(&mut self, node: Rc<Node>) -> EvalResult {
+	pub fn doc(&self, symbol: &str) -> Option<String> {
+		if let Some(&(_, doc)) = SPECIAL_FORMS.iter().find(|&&(name, _)| name == symbol) {
+			return Some(doc.to_string());
+		}
+
+		let value = Symbol::find(symbol).and_then(|symbol| self.lookup(symbol));
+		match value.as_deref() {
+			Some(&Value::Func(ref lambda)) => {
+				let mut doc: Vec<String> = lambda.arities.iter()
+					.map(|arity| format!("({} {})", symbol, arity.params.signature()).replace(" )", ")"))
+					.collect();
+				if let Some(ref text) = lambda.doc {
+					doc.push(text.clone());
+				}
+				Some(doc.join(" "))
+			},
+			Some(&Value::Macro(ref mac)) => {
+				let mut doc: Vec<String> = mac.clauses.iter()
+					.map(|clause| format!("({} {})", symbol, clause.signature()).replace(" )", ")"))
+					.collect();
+				if let Some(ref text) = mac.doc {
+					doc.push(text.clone());
+				}
+				Some(doc.join(" "))
+			},
+			Some(&Value::ExternalFn(_)) => self.docs.get(symbol).map(|doc| doc.to_string()),
+			Some(value) => Some(format!("{}: {}", symbol, value.type_name())),
+			None => None
+		}
+	}
+
+	pub fn define(&mut self, name: &str, value: Rc<Value>) {
+		self.env.define_global(Symbol::intern(name), value);
+	}
+
+	// The value of a global definition.
+	pub fn get(&self, name: &str) -> Option<Rc<Value>> {
+		Symbol::find(name).and_then(|name| self.env.lookup_global(name))
+	}
+
+	// Evaluates all expressions in source and returns their values.
+	pub fn eval_all(&mut self, source: &str) -> Result<Vec<Rc<Value>>, Error> {
+		let nodes: Vec<Rc<Node>> = match parser::parse(&source.to_string()) {
+			Ok(nodes) => nodes.into_iter().map(Rc::new).collect(),
+			Err(e) => { return Err(e.into()); }
+		};
+
+		analyzer::declare(self, &nodes);
+		let mut values: Vec<Rc<Value>> = vec![];
+		for node in nodes {
+			match self.eval(node) {
+				Ok(value) => values.push(value),
+				Err(e) => { return Err(e); }
+			}
+		}
+		Ok(values)
+	}
+
+	// Evaluates source and returns the value of the last expression.
+	pub fn eval_str(&mut self, source: &str) -> EvalResult {
+		match self.eval_all(source) {
+			Ok(mut values) => Ok(values.pop().unwrap_or_else(|| Rc::new(Value::Nil))),
+			Err(e) => Err(e)
+		}
+	}
+
+	// Evaluates the file (imports are relative to it) and returns the value
+	// of the last expression.
+	pub fn eval_file<P: AsRef<Path>>(&mut self, path: P) -> EvalResult {
+		let path = path.as_ref();
+		let path = match path.canonicalize() {
+			Ok(path) => path,
+			Err(e) => { return Err(format!("{}: {}", path.display(), e).into()); }
+		};
+		let mut buf = String::new();
+		match File::open(&path).and_then(|mut file| file.read_to_string(&mut buf)) {
+			Ok(_) => {},
+			Err(e) => { return Err(format!("{}: {}", path.display(), e).into()); }
+		}
+
+		let nodes: Vec<Rc<Node>> = match parser::parse(&buf) {
+			Ok(nodes) => nodes.into_iter().map(Rc::new).collect(),
+			Err(e) => { return Err(format!("{}:{}", path.display(), e).into()); }
+		};
+
+		self.modules.enter(&path)?;
+		analyzer::declare(self, &nodes);
+		let mut res = Ok(Rc::new(Value::Nil));
+		for node in nodes {
+			res = self.eval(node);
+			if res.is_err() {
+				break;
+			}
+		}
+		self.modules.exit();
+		res
+	}
+
+	// Analyzes and evaluates a top level expression with the backend.
+	pub(crate) fn eval(&mut self, node: Rc<Node>) -> EvalResult {
+		let node = analyzer::analyze(self, &node)?;
+		match self.backend {
+			Backend::TreeWalker => self.eval_node(node),
+			Backend::Vm => vm::eval(self, &node)
+		}
+	}
+
+	// Names defined inside of a module live in the global scope
+	// prefixed with the namespace of the module: `math/square`.
+	fn qualify(&self, name: Symbol) -> Symbol {
+		match self.ns {
+			Some(ref ns) => Symbol::intern(&format!("{}/{}", ns, name)),
+			None => name
+		}
+	}
+
+	pub(crate) fn namespace(&self) -> Option<Rc<String>> {
+		self.ns.clone()
+	}
+
+	pub(crate) fn swap_ns(&mut self, ns: Option<Rc<String>>) -> Option<Rc<String>> {
+		mem::replace(&mut self.ns, ns)
+	}
+
+	pub(crate) fn define_qualified(&mut self, name: Symbol, value: Rc<Value>) -> Rc<Value> {
+		let value = named(name, value);
+		let key = self.qualify(name);
+		self.env.define_global(key, value.clone());
+		value
+	}
+
+	fn lookup(&self, symbol: Symbol) -> Option<Rc<Value>> {
+		self.resolve(symbol).and_then(|slot| self.env.global(slot).ok())
+	}
+
+	// The slot of symbol in the current namespace, the aliases or the globals.
+	pub(crate) fn resolve(&self, symbol: Symbol) -> Option<usize> {
+		// only `def` and `declare` intern qualified names, a name that was
+		// never interned cannot be defined in the namespace
+		if let Some(ref ns) = self.ns {
+			if let Some(slot) = Symbol::find(&format!("{}/{}", ns, symbol)).and_then(|key| self.env.slot(key)) {
+				return Some(slot);
+			}
+		}
+
+		if let Some(name) = self.modules.resolve_alias(&self.ns, symbol.as_str()) {
+			return Symbol::find(&name).and_then(|name| self.env.slot(name));
+		}
+
+		self.env.slot(symbol)
+	}
+
+	// Declares the global `name` in the current namespace.
+	pub(crate) fn declare(&mut self, name: Symbol) -> usize {
+		let key = self.qualify(name);
+		self.env.declare(key)
+	}
+
+	pub(crate) fn global(&self, slot: usize) -> EvalResult {
+		self.env.global(slot)
+	}
+
+	pub(crate) fn set_global(&mut self, slot: usize, value: Rc<Value>) -> Result<(), String> {
+		self.env.set_global(slot, value)
+	}
+
+	// Evaluates an analyzed node by walking the tree.
+	pub(crate) fn eval_node(&mut self, node: Rc<Node>) -> EvalResult {
 		match *node {
 			Node::Str(ref value) => Ok(Rc::new( Value::Str( value.clone() ) )),
 			Node::Int(value) => Ok(Rc::new( Value::Int(value) )),
 			Node::Real(value) => Ok(Rc::new( Value::Real(value) )),
 			Node::Bool(value) => Ok(Rc::new( Value::Bool(value) )),
 			Node::Nil => Ok(Rc::new(Value::Nil)),
+			Node::Keyword(ref name) => Ok(Rc::new( Value::Keyword(name.clone()) )),
 			Node::List(ref nodes) => {
-				let mut list: LinkedList<Rc<Value>> = LinkedList::new();
+				let mut list: Vector<Rc<Value>> = Vector::new();
 				for node in nodes {
-					match self.eval(node.clone()) {
+					match self.eval_node(node.clone()) {
 						Ok(value) => list.push_back(value),
 						Err(e) => {
 							return Err(e);
@@ -166,9 +687,9 @@ impl Interpreter {
 				Ok(Rc::new( Value::List(list) ))
 			},
 			Node::Map(ref nodes) => {
-				let mut map: HashMap<String, Rc<Value>> = HashMap::new();
+				let mut map: Map<String, Rc<Value>> = Map::default();
 				for (key, node) in nodes {
-					match self.eval(node.clone()) {
+					match self.eval_node(node.clone()) {
 						Ok(value) => {
 							map.insert(key.clone(), value);
 						},
@@ -179,156 +700,473 @@ impl Interpreter {
 				}
 				Ok(Rc::new( Value::Map(map) ))
 			},
-			Node::Symbol(ref symbol) => {
-				if let Some(value) = self.env.lookup(symbol) {
+			Node::Set(ref nodes) => {
+				let mut set: Set<Rc<Value>> = Set::default();
+				for node in nodes {
+					set.insert(self.eval_node(node.clone())?);
+				}
+				Ok(Rc::new( Value::Set(set) ))
+			},
+			Node::Local(depth, index) => match self.env.local(depth, index) {
+				Some(value) => Ok(value),
+				None => Err("unbound local variable".into())
+			},
+			Node::Global(slot) => self.env.global(slot),
+			Node::Symbol(symbol) => {
+				if let Some(value) = self.lookup(symbol) {
 					Ok(value)
 				} else {
-					Err("unkown symbol".to_string())
+					Err(format!("unknown symbol `{}`", symbol).into())
 				}
 			},
 			Node::Expr(ref args) => self.eval_expr(args)
 		}
 	}
 
-	fn eval_expr(&mut self, args: &LinkedList<Rc<Node>>) -> EvalResult {
+	fn eval_expr(&mut self, args: &Vector<Rc<Node>>) -> EvalResult {
 		let mut iter: IntoIter<Rc<Node>> = args.clone().into_iter();
 		if let Some(op) = iter.next() {
 			match *(op.clone()) {
-				Node::Symbol(ref symname) => {
-					match symname.as_str() {
-						"fn" => self.eval_fn(iter),
-						"def" => self.eval_def(iter),
-						"if" => self.eval_if(iter),
-						"and" => self.eval_and(iter),
-						"or" => self.eval_or(iter),
+				Node::Symbol(symname) => {
+					match symname {
+						symbol::FN => self.eval_fn(iter),
+						symbol::DEF => self.eval_def(iter),
+						symbol::SET => self.eval_set(iter),
+						symbol::DEFMACRO => self.eval_defmacro(iter),
+						symbol::DO => self.eval_do(iter),
+						symbol::IF => self.eval_if(iter),
+						symbol::AND => self.eval_and(iter),
+						symbol::OR => self.eval_or(iter),
+						symbol::IMPORT => self.eval_import(iter),
+						symbol::TRY => self.eval_try(iter),
+						symbol::MATCH => self.eval_match(iter),
+						symbol::APPLY => self.eval_apply(iter),
 						_ => {
-							if let Some(value) = self.env.lookup(symname) {
+							if let Some(value) = self.lookup(symname) {
 								self.eval_value(value, iter)
 							} else {
-								Err("unkown symbol".to_string())
+								Err(format!("unknown symbol `{}`", symname).into())
 							}
 						}
 					}
 				},
 				_ => {
-					match self.eval(op) {
+					match self.eval_node(op) {
 						Ok(value) => self.eval_value(value, iter),
 						Err(e) => Err(e)
 					}
 				}
 			}
 		} else {
-			Err("cannot evaluate empty expression".to_string())
+			Err("cannot evaluate empty expression".into())
 		}
 	}
 
 	fn eval_value(&mut self, value: Rc<Value>, mut iter: IntoIter<Rc<Node>>) -> EvalResult {
 		match *value {
-			Value::ExternalFn(func) => {
+			Value::ExternalFn(_) | Value::Func(_) => {
 				let mut args: Vec<Rc<Value>> = vec![];
 				while let Some(node) = iter.next() {
-					match self.eval(node) {
+					match self.eval_node(node) {
 						Ok(value) => args.push(value),
-						Err(e) => {
-							return Err(e);
-						}
+						Err(e) => { return Err(e); }
 					}
 				}
-				func(args)
+				self.apply(value, args)
 			},
-			Value::Func(ref argnames, ref nodes) => {
-				self.env.enter();
-				let mut i = 0;
-				while let Some(argnode) = iter.next() {
-					match self.eval(argnode) {
-						Ok(value) => self.env.define(argnames[i].clone(), value),
-						Err(e) => { return Err(e); }
-					}
-					i += 1;
+			Value::Macro(ref mac) => {
+				// only macros that were not known when the expression was analyzed
+				match macros::expand(&mac.clauses, iter.collect()) {
+					Ok(node) => {
+						let node = analyzer::analyze(self, &node)?;
+						self.eval_node(node)
+					},
+					Err(e) => Err(e.into())
 				}
+			},
+			_ => Err("cannot evaluate expression".into())
+		}
+	}
 
-				let mut return_value: Rc<Value> = Rc::new( Value::Nil );
-				for node in nodes {
-					return_value = match self.eval(node.clone()) {
-						Ok(value) => value,
-						Err(e) => { return Err(e); }
-					}
+	// Calls a function with evaluated arguments.
+	pub(crate) fn apply(&mut self, value: Rc<Value>, args: Vec<Rc<Value>>) -> EvalResult {
+		match *value {
+			Value::ExternalFn(func) => func(args),
+			Value::Func(ref lambda) => {
+				let arity = lambda.arity(args.len())?;
+				if let Some(ref code) = arity.code {
+					return vm::call(self, code.clone(), lambda.ns.clone(), args);
 				}
-				self.env.exit();
-				Ok(return_value)
+
+				let argc = args.len();
+				let caller = self.env.enter(arity.params.bind(args), lambda.closure.clone());
+
+				// the body is evaluated in the namespace the function was defined in
+				let caller_ns = mem::replace(&mut self.ns, lambda.ns.clone());
+				let res = self.eval_params(&arity.params, argc).and_then(|_| self.eval_body(&arity.body));
+				self.ns = caller_ns;
+				self.env.exit(caller);
+				res
 			},
-			_ => Err("cannot evaluate expression".to_string())
+			_ => Err("cannot evaluate expression".into())
 		}
 	}
 
-	fn eval_fn(&mut self, mut iter: IntoIter<Rc<Node>>) -> EvalResult {
-		let mut args: Vec<String> = vec![];
+	fn eval_fn(&mut self, iter: IntoIter<Rc<Node>>) -> EvalResult {
+		let args: Vec<Rc<Node>> = iter.collect();
+		let form = params::parse_fn(&args)?;
+		Ok( Rc::new( Value::Func(Lambda {
+			name: None,
+			arities: form.arities.into_iter().map(|(params, body)| Arity { params: params, body: body, code: None }).collect(),
+			ns: self.ns.clone(),
+			closure: self.env.frame(),
+			doc: form.doc
+		}) ) )
+	}
+
+	// Evaluates the defaults of the optional parameters that were not passed
+	// and binds the variables of the patterns, in the frame of the function.
+	fn eval_params(&mut self, params: &Params, argc: usize) -> Result<(), Error> {
+		for (i, default) in params.defaults.iter().enumerate() {
+			let slot = params.required + i;
+			if slot >= argc {
+				let value = self.eval_node(default.clone())?;
+				self.env.set_local(0, slot, value)?;
+			}
+		}
+		if params.patterns.len() == 0 {
+			return Ok(());
+		}
+		let values = params.destructure(&self.env.frame().unwrap().values.borrow())?;
+		for (i, value) in values.into_iter().enumerate() {
+			self.env.set_local(0, params.slots() + i, value)?;
+		}
+		Ok(())
+	}
+
+	fn eval_body(&mut self, nodes: &[Rc<Node>]) -> EvalResult {
+		let mut res = Ok(Rc::new( Value::Nil ));
+		for node in nodes {
+			res = self.eval_node(node.clone());
+			if res.is_err() {
+				break;
+			}
+		}
+		res
+	}
+
+	fn eval_do(&mut self, iter: IntoIter<Rc<Node>>) -> EvalResult {
+		let nodes: Vec<Rc<Node>> = iter.collect();
+		self.eval_body(&nodes)
+	}
+
+	// (defmacro name "doc" {params} template) or, with multiple clauses,
+	// (defmacro name "doc" ({params} template) ({params} template) ...)
+	fn eval_defmacro(&mut self, mut iter: IntoIter<Rc<Node>>) -> EvalResult {
+		let key: Symbol;
 		if let Some(node) = iter.next() {
 			match *node {
-				Node::List(ref list) => {
-					for node in list {
-						match *node.clone() {
-							Node::Symbol(ref sym) => { args.push(sym.clone()); },
-							_ => { return Err("illegal fn syntax".to_string()); }
-						}
+				Node::Symbol(sym) => { key = sym; },
+				_ => { return Err("illegal defmacro syntax".into()); }
+			}
+		} else { return Err("illegal defmacro syntax".into()); }
+
+		let mut nodes: Vec<Rc<Node>> = iter.collect();
+		let mut doc: Option<String> = None;
+		if let Some(&Node::Str(ref text)) = nodes.first().map(|node| &**node) {
+			doc = Some(text.clone());
+		}
+		if doc.is_some() {
+			nodes.remove(0);
+		}
+
+		let mut clauses: Vec<Clause> = vec![];
+		match nodes.first().map(|node| &**node) {
+			Some(&Node::List(ref params)) if nodes.len() == 2 => {
+				match Clause::new(params, nodes[1].clone()) {
+					Ok(clause) => clauses.push(clause),
+					Err(e) => { return Err(e.into()); }
+				}
+			},
+			_ => {
+				for node in &nodes {
+					let clause: Vec<Rc<Node>> = match **node {
+						Node::Expr(ref items) => items.iter().cloned().collect(),
+						_ => { return Err("illegal defmacro syntax".into()); }
+					};
+					match (clause.len(), clause.first().map(|node| &**node)) {
+						(2, Some(&Node::List(ref params))) => {
+							match Clause::new(params, clause[1].clone()) {
+								Ok(clause) => clauses.push(clause),
+								Err(e) => { return Err(e.into()); }
+							}
+						},
+						_ => { return Err("illegal defmacro syntax".into()); }
 					}
-				},
-				_ => { return Err("illegal fn syntax".to_string()); }
+				}
 			}
-		} else { return Err("illegal fn syntax".to_string()); }
-		Ok( Rc::new( Value::Func(args, iter.collect()) ) )
+		}
+
+		if clauses.len() == 0 {
+			return Err("illegal defmacro syntax".into());
+		}
+
+		let value = Rc::new(Value::Macro(Macro { clauses: clauses, doc: doc }));
+		let key = self.qualify(key);
+		self.env.define_global(key, value.clone());
+		Ok(value)
 	}
 
 	fn eval_def(&mut self, mut iter: IntoIter<Rc<Node>>) -> EvalResult {
-		let key: String;
+		let key: Symbol;
 		if let Some(node) = iter.next() {
 			match *node {
-				Node::Symbol(ref sym) => { key = sym.clone(); },
-				_ => { return Err("illegal def syntax".to_string()); }
+				Node::Symbol(sym) => { key = sym; },
+				_ => { return Err("illegal def syntax".into()); }
 			}
-		} else { return Err("illegal def syntax".to_string()); }
+		} else { return Err("illegal def syntax".into()); }
 
 		let value: Rc<Value>;
 		if let Some(node) = iter.next() {
-			match self.eval(node) {
+			match self.eval_node(node) {
 				Ok(val) => { value = val; },
 				Err(e) => { return Err(e); }
 			}
-		} else { return Err("illegal def syntax".to_string()); }
+		} else { return Err("illegal def syntax".into()); }
 
 		if iter.count() != 0 {
-			return Err("illegal def syntax".to_string());
+			return Err("illegal def syntax".into());
 		}
 
-		self.env.define_global(key, value.clone());
+		Ok(self.define_qualified(key, value))
+	}
+
+	// (set! target x), the analyzer resolved target to a variable
+	fn eval_set(&mut self, mut iter: IntoIter<Rc<Node>>) -> EvalResult {
+		let (target, node) = match (iter.next(), iter.next(), iter.next()) {
+			(Some(target), Some(node), None) => (target, node),
+			_ => { return Err("illegal set! syntax".into()); }
+		};
+
+		let value = self.eval_node(node)?;
+		match *target {
+			Node::Local(depth, index) => self.env.set_local(depth, index, value.clone())?,
+			Node::Global(slot) => self.env.set_global(slot, value.clone())?,
+			Node::Symbol(name) => { return Err(format!("cannot set! `{}`, it is not defined", name).into()); },
+			_ => { return Err("illegal set! syntax".into()); }
+		}
 		Ok(value)
 	}
 
+	fn eval_apply(&mut self, iter: IntoIter<Rc<Node>>) -> EvalResult {
+		let mut values: Vec<Rc<Value>> = vec![];
+		for node in iter {
+			values.push(self.eval_node(node)?);
+		}
+		if values.len() < 2 {
+			return Err("illegal apply syntax".into());
+		}
+		let func = values.remove(0);
+		self.apply_list(func, values)
+	}
+
+	// Calls func with args, the last of which is a list of more arguments.
+	pub(crate) fn apply_list(&mut self, func: Rc<Value>, mut args: Vec<Rc<Value>>) -> EvalResult {
+		let last = args.pop().unwrap();
+		match *last {
+			Value::List(ref items) => args.extend(items.iter().cloned()),
+			_ => { return Err(format!("apply takes a list as its last argument, got {}", last.type_name()).into()); }
+		}
+		self.apply(func, args)
+	}
+
+	// (try body (catch kind handler)... (finally cleanup)) as rewritten by the
+	// analyzer, body, handler and cleanup are functions
+	fn eval_try(&mut self, mut iter: IntoIter<Rc<Node>>) -> EvalResult {
+		let body = match iter.next() {
+			Some(node) => self.eval_node(node)?,
+			None => { return Err("illegal try syntax".into()); }
+		};
+
+		let mut catches: Vec<(Rc<Value>, Rc<Value>)> = vec![];
+		let mut finally: Option<Rc<Value>> = None;
+		for node in iter {
+			let clause: Vec<Rc<Node>> = match *node {
+				Node::Expr(ref clause) => clause.iter().cloned().collect(),
+				_ => { return Err("illegal try syntax".into()); }
+			};
+			match (clause.len(), clause.first().map(|node| &**node)) {
+				(3, Some(&Node::Symbol(symbol::CATCH))) => {
+					let kind = self.eval_node(clause[1].clone())?;
+					catches.push((kind, self.eval_node(clause[2].clone())?));
+				},
+				(2, Some(&Node::Symbol(symbol::FINALLY))) => { finally = Some(self.eval_node(clause[1].clone())?); },
+				_ => { return Err("illegal try syntax".into()); }
+			}
+		}
+		self.try_catch(body, catches, finally)
+	}
+
+	fn eval_match(&mut self, iter: IntoIter<Rc<Node>>) -> EvalResult {
+		let mut values: Vec<Rc<Value>> = vec![];
+		for node in iter {
+			values.push(self.eval_node(node)?);
+		}
+		if values.len().is_multiple_of(2) {
+			return Err("illegal match syntax".into());
+		}
+		let clauses = values[1..].chunks(2).map(|clause| (clause[0].clone(), clause[1].clone())).collect();
+		let body = self.select_clause(&values[0], clauses)?;
+		self.apply(body, vec![values[0].clone()])
+	}
+
+	// The body of the first clause whose pattern matches value and whose guard
+	// (nil if there is none) is true. Bodies and guards are functions of one
+	// parameter, the pattern.
+	pub(crate) fn select_clause(&mut self, value: &Rc<Value>, clauses: Vec<(Rc<Value>, Rc<Value>)>) -> EvalResult {
+		for (body, guard) in clauses {
+			let matches = match *body {
+				Value::Func(ref lambda) => {
+					let params = &lambda.arity(1)?.params;
+					params.matches(&params.bind(vec![value.clone()]))
+				},
+				_ => false
+			};
+			if !matches {
+				continue;
+			}
+			if let Value::Nil = *guard {} else if !self.apply(guard, vec![value.clone()])?.to_bool() {
+				continue;
+			}
+			return Ok(body);
+		}
+		Err(format!("no clause of match matches {}", value.to_string()).into())
+	}
+
+	// Calls body, an error is passed to the handler of the first catch that
+	// matches it, cleanup is called in any case (and its errors win).
+	pub(crate) fn try_catch(&mut self, body: Rc<Value>, catches: Vec<(Rc<Value>, Rc<Value>)>, cleanup: Option<Rc<Value>>) -> EvalResult {
+		let mut res: Result<Rc<Value>, Rc<Value>> = self.apply(body, vec![]).map_err(errors::caught);
+		if let Err(error) = res.clone() {
+			for (kind, handler) in catches {
+				let matches = match *kind {
+					Value::Keyword(ref kind) => Ok(errors::is_kind(kind, &error)),
+					Value::Func(_) | Value::ExternalFn(_) => self.apply(kind.clone(), vec![error.clone()]).map(|value| value.to_bool()),
+					_ => Err(format!("catch takes a keyword or a predicate, got {}", kind.type_name()).into())
+				};
+				match matches {
+					Ok(true) => {
+						res = self.apply(handler, vec![error]).map_err(errors::caught);
+						break;
+					},
+					Ok(false) => {},
+					Err(e) => {
+						res = Err(errors::caught(e));
+						break;
+					}
+				}
+			}
+		}
+
+		if let Some(cleanup) = cleanup {
+			self.apply(cleanup, vec![])?;
+		}
+		res.map_err(errors::raise)
+	}
+
+	fn eval_import(&mut self, mut iter: IntoIter<Rc<Node>>) -> EvalResult {
+		if self.sandboxed {
+			return Err("import is not allowed in a sandbox".into());
+		}
+
+		let target: String;
+		if let Some(node) = iter.next() {
+			match *node {
+				Node::Symbol(sym) => { target = format!("{}.{}", sym, modules::EXTENSION); },
+				Node::Str(ref path) => { target = path.clone(); },
+				_ => { return Err("illegal import syntax".into()); }
+			}
+		} else { return Err("illegal import syntax".into()); }
+
+		let mut only: Vec<Symbol> = vec![];
+		let mut alias: Option<String> = None;
+		while let Some(node) = iter.next() {
+			match (&*node, iter.next().as_deref()) {
+				(&Node::Keyword(ref kw), Some(&Node::List(ref names))) if kw == "only" => {
+					for name in names {
+						match **name {
+							Node::Symbol(sym) => { only.push(sym); },
+							_ => { return Err("illegal import syntax".into()); }
+						}
+					}
+				},
+				(&Node::Keyword(ref kw), Some(&Node::Symbol(sym))) if kw == "as" => {
+					alias = Some(sym.to_string());
+				},
+				_ => { return Err("illegal import syntax".into()); }
+			}
+		}
+
+		let path = self.modules.resolve(&target)?;
+
+		let ns = match self.modules.namespace(&path) {
+			Some(ns) => ns,
+			None => {
+				let ns = modules::namespace_of(&path)?;
+				self.modules.check_namespace(&path, &ns)?;
+
+				// registered once it is loaded, importing a module that is
+				// still loading is an import cycle (detected by eval_file)
+				let importer_ns = self.ns.replace(ns.clone());
+				let res = self.eval_file(&path);
+				self.ns = importer_ns;
+				res?;
+				self.modules.register(&path, ns.clone());
+				ns
+			}
+		};
+
+		if let Some(alias) = alias {
+			self.modules.alias(self.ns.clone(), alias, ns.clone());
+		}
+
+		for name in only {
+			match Symbol::find(&format!("{}/{}", ns, name)).and_then(|key| self.env.lookup_global(key)) {
+				Some(value) => {
+					let key = self.qualify(name);
+					self.env.define_global(key, value);
+				},
+				None => { return Err(format!("`{}` is not defined in {}", name, ns).into()); }
+			}
+		}
+		Ok(Rc::new(Value::Nil))
+	}
+
 	fn eval_if(&mut self, mut iter: IntoIter<Rc<Node>>) -> EvalResult {
 		let cond: bool;
 		if let Some(node) = iter.next() {
-			match self.eval(node) {
+			match self.eval_node(node) {
 				Ok(value) => { cond = value.to_bool(); },
 				Err(e) => { return Err(e); }
 			}
-		} else { return Err("illegal if syntax".to_string()); }
+		} else { return Err("illegal if syntax".into()); }
 
 		if let Some(if_true) = iter.next() {
 			if cond {
-				self.eval(if_true)
+				self.eval_node(if_true)
 			} else if let Some(if_false) = iter.next() {
-				self.eval(if_false)
+				self.eval_node(if_false)
 			} else {
 				Ok(Rc::new(Value::Nil))
 			}
 		} else {
-			Err("illegal if syntax".to_string())
+			Err("illegal if syntax".into())
 		}
 	}
 
 	fn eval_and(&mut self, mut iter: IntoIter<Rc<Node>>) -> EvalResult {
 		while let Some(node) = iter.next() {
-			match self.eval(node) {
+			match self.eval_node(node) {
 				Ok(value) => {
 					if !value.to_bool() {
 						return Ok(Rc::new(Value::Bool(false)));
@@ -342,7 +1180,7 @@ impl Interpreter {
 
 	fn eval_or(&mut self, mut iter: IntoIter<Rc<Node>>) -> EvalResult {
 		while let Some(node) = iter.next() {
-			match self.eval(node) {
+			match self.eval_node(node) {
 				Ok(value) => {
 					if value.to_bool() {
 						return Ok(Rc::new(Value::Bool(true)));
@@ -355,3 +1193,112 @@ impl Interpreter {
 	}
 
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+	use std::fs;
+	use testing::{for_each_backend, ok};
+
+	fn eval(interpreter: &mut Interpreter, raw: &str) -> EvalResult {
+		let mut res = Ok(Rc::new(Value::Nil));
+		for node in parser::parse(&raw.to_string()).unwrap() {
+			res = interpreter.eval(Rc::new(node));
+		}
+		res
+	}
+
+	#[test]
+	fn prelude() {
+		let mut interpreter = Interpreter::new();
+		assert_eq!(eval(&mut interpreter, "(inc (dec 42))"), Ok(Rc::new(Value::Int(42))));
+		assert_eq!(eval(&mut interpreter, "(not nil)"), Ok(Rc::new(Value::Bool(true))));
+		assert_eq!(eval(&mut interpreter, "((comp inc inc) 1)"), Ok(Rc::new(Value::Int(3))));
+		assert_eq!(eval(&mut interpreter, "((partial * 2) 21)"), Ok(Rc::new(Value::Int(42))));
+		assert_eq!(eval(&mut interpreter, "((comp list inc +) 1 2 3)"), Ok(Rc::new(Value::List(vec![Rc::new(Value::Int(7))].into()))));
+		assert_eq!(eval(&mut interpreter, "((partial + 1 2) 3 4)"), Ok(Rc::new(Value::Int(10))));
+		assert_eq!(eval(&mut interpreter, "((partial list))"), Ok(Rc::new(Value::List(Vector::new()))));
+		assert_eq!(eval(&mut interpreter, "(cond false 1 (= 1 2) 2 :else 3)"), Ok(Rc::new(Value::Int(3))));
+		assert_eq!(eval(&mut interpreter, "(when false (slurp \"missing\"))"), Ok(Rc::new(Value::Nil)));
+		assert_eq!(eval(&mut interpreter, "(when false (undefined))"), Err("unknown symbol `undefined`".into()));
+		assert_eq!(eval(&mut interpreter, "(unless false 1 2)"), Ok(Rc::new(Value::Int(2))));
+
+		let mut interpreter = Interpreter::without_prelude();
+		assert!(eval(&mut interpreter, "(inc 1)").is_err());
+	}
+
+	#[test]
+	fn closures() {
+		let mut interpreter = Interpreter::new();
+		eval(&mut interpreter, "(defn adder {n} (fn {x} (+ x n)))").unwrap();
+		assert_eq!(eval(&mut interpreter, "((adder 10) 5)"), Ok(Rc::new(Value::Int(15))));
+	}
+
+	#[test]
+	fn env() {
+		let mut env = Env::new();
+		let x = Symbol::intern("x");
+		let slot = env.declare(x);
+		assert_eq!(env.set_global(slot, Rc::new(Value::Int(1))), Err("cannot set! `x`, it is not defined yet".to_string()));
+		env.define_global(x, Rc::new(Value::Int(1)));
+		assert_eq!(env.set_global(slot, Rc::new(Value::Int(2))), Ok(()));
+		assert_eq!(env.lookup_global(x), Some(Rc::new(Value::Int(2))));
+
+		// closures share the frame they were created in
+		let caller = env.enter(vec![Rc::new(Value::Int(1))], None);
+		let frame = env.frame();
+		env.enter(vec![], frame.clone());
+		assert_eq!(env.set_local(1, 0, Rc::new(Value::Int(3))), Ok(()));
+		assert_eq!(env.set_local(0, 0, Rc::new(Value::Int(3))), Err("unbound local variable".to_string()));
+		env.exit(frame);
+		assert_eq!(env.local(0, 0), Some(Rc::new(Value::Int(3))));
+		env.exit(caller);
+		assert_eq!(env.local(0, 0), None);
+	}
+
+	#[test]
+	fn set() {
+		for_each_backend(|eval| {
+			assert_eq!(eval("(def x 1) (set! x (+ x 1)) x"), ok("2"));
+			assert_eq!(eval("(defn f {x} (set! x (* x 10)) x) { (f 3) x }"), ok("{ 30 2 }"));
+
+			// closures see assignments to the variables they captured and the other way around
+			assert_eq!(eval("(defn counter {n} (fn {} (set! n (inc n)))) (def c (counter 0)) (c) (c) (c)"), ok("3"));
+			assert_eq!(eval("(defn g {n} ((fn {get} ((fn {} (set! n 5))) (get)) (fn {} n))) (g 1)"), ok("5"));
+			assert_eq!(eval("(defn h {n} (set! x n)) (h 7) x"), ok("7"));
+
+			assert_eq!(eval("(set! y 1)"), Err("cannot set! `y`, it is not defined".to_string()));
+			assert_eq!(eval("(defn k {} (set! later 1)) (k) (def later 0)"), Err("cannot set! `later`, it is not defined yet".to_string()));
+			assert_eq!(eval("(defn m {} (def z 1))"), Err("cannot def `z` inside of a function, use set! to assign to a variable".to_string()));
+			assert_eq!(eval("m"), Err("`m` is not defined yet".to_string()));
+		});
+	}
+
+	#[test]
+	fn imports() {
+		let dir = env::temp_dir().join(format!("lial-imports-{}", ::std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let dir = dir.canonicalize().unwrap();
+		fs::write(dir.join("math.lial"), "(def loads (atom 0)) (swap! loads inc) (defn square {x} (* x x))").unwrap();
+		fs::write(dir.join("main.lial"), "(import math) (import math :as m) (list @math/loads (m/square 3))").unwrap();
+		fs::write(dir.join("a.lial"), "(def x 1) (import b)").unwrap();
+		fs::write(dir.join("b.lial"), "(import a) (def y a/x)").unwrap();
+
+		// a module is loaded once, importing it again only adds the alias
+		let mut interpreter = Interpreter::new();
+		assert_eq!(interpreter.eval_file(dir.join("main.lial")), interpreter.eval_str("{ 1 9 }"));
+		// looking up globals inside of the module does not intern qualified names
+		assert!(Symbol::find("math/square").is_some());
+		assert_eq!(Symbol::find("math/*"), None);
+		assert_eq!(Symbol::find("math/inc"), None);
+
+		// importing a module that is still loading is a cycle, not a half loaded namespace
+		let (a, b) = (dir.join("a.lial").display().to_string(), dir.join("b.lial").display().to_string());
+		let cycle = Err(format!("import cycle: {} -> {} -> {}", a, b, a).into());
+		assert_eq!(interpreter.eval_str(&format!("(import {:?})", a)), cycle.clone());
+		assert_eq!(interpreter.eval_file(&a), cycle);
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,75 @@
+use std::rc::Rc;
+
+use serde_json;
+
+use interpreter::{Value, EvalResult, Builtins};
+
+// JSON objects become maps, arrays lists and null nil, numbers outside of the
+// range of ints become reals. Map keys are strings
+// in both languages, keywords are written as strings. Functions, macros and
+// builtins cannot be written at all, NaN and infinite reals become null.
+pub const BUILTINS: &Builtins = &[
+	("json/parse", parse, "(json/parse s) Parses a JSON string into maps, lists, numbers, strings, bools and nil."),
+	("json/stringify", stringify, "(json/stringify x) (json/stringify x :pretty) Converts x to a JSON string, indented with :pretty.")
+];
+
+pub fn parse(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 1 {
+		return Err("json/parse takes only one argument of type string".into());
+	}
+
+	match *args[0] {
+		Value::Str(ref source) => match serde_json::from_str::<Value>(source) {
+			Ok(value) => Ok(Rc::new(value)),
+			Err(e) => Err(format!("json/parse: {}", e).into())
+		},
+		_ => Err("json/parse only takes string as argument".into())
+	}
+}
+
+pub fn stringify(args: Vec<Rc<Value>>) -> EvalResult {
+	let pretty = match args.len() {
+		1 => false,
+		2 => match *args[1] {
+			Value::Keyword(ref option) if option == "pretty" => true,
+			_ => return Err(format!("json/stringify: unknown option {}", args[1].to_string()).into())
+		},
+		_ => return Err("json/stringify takes a value and optionally :pretty".into())
+	};
+
+	let res = if pretty {
+		serde_json::to_string_pretty(&*args[0])
+	} else {
+		serde_json::to_string(&*args[0])
+	};
+	match res {
+		Ok(json) => Ok(Rc::new(Value::Str(json))),
+		Err(e) => Err(format!("json/stringify: {}", e).into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use testing::{eval, printed, string};
+
+	#[test]
+	fn json() {
+		assert_eq!(eval("(json/stringify { 1 2.5 \"a\" true nil :k })"), Ok(string("[1,2.5,\"a\",true,null,\"k\"]")));
+		assert_eq!(eval("(json/stringify { a: { b: { } } } :pretty)"), Ok(string("{\n  \"a\": {\n    \"b\": []\n  }\n}")));
+		assert_eq!(eval("(json/stringify { zeta: 1 alpha: 2 mid: 3 })"), Ok(string("{\"alpha\":2,\"mid\":3,\"zeta\":1}")));
+		assert_eq!(eval("(json/stringify { f: inc })"), Err("json/stringify: cannot serialize a value of type fn".into()));
+		assert!(eval("(json/stringify 1 :ugly)").is_err());
+
+		let value = eval("(json/parse \"{\\\"a\\\": [1, 2.5, null, false], \\\"b\\\": {}}\")").unwrap();
+		assert_eq!(value, eval("{ a: { 1 2.5 nil false } b: {:} }").unwrap());
+		// numbers that do not fit into an int become reals
+		let numbers = "(json/parse \"[9223372036854775807, -9223372036854775808, 18446744073709551615, -9223372036854775809]\")";
+		assert_eq!(printed(numbers), "{ 9223372036854775807 -9223372036854775808 18446744073709552000 -9223372036854776000 }");
+		assert_eq!(eval(&format!("(nth {} 2)", numbers)), eval("18446744073709551615.0"));
+		assert!(eval("(json/parse \"[1, 2\")").unwrap_err().message().starts_with("json/parse: EOF while parsing a list"));
+
+		let source = "{ name: \"lial\" tags: { \"a\" \"b\" } version: { major: 0 minor: 1 } }";
+		let roundtrip = format!("(json/parse (json/stringify {}))", source);
+		assert_eq!(eval(&roundtrip), eval(source));
+	}
+}
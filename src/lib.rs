@@ -0,0 +1,69 @@
+//! Lial is a small lisp-like language. The interpreter can be embedded:
+//!
+//! ```
+//! use lial::{Interpreter, Value};
+//!
+//! let mut interpreter = Interpreter::new();
+//! interpreter.define("answer", Value::from(41).into());
+//! let value = interpreter.eval_str("(inc answer)").unwrap();
+//! assert_eq!(*value, Value::from(42));
+//! assert_eq!(lial::eval_str("(+ 1 2)").unwrap(), Value::from(3).into());
+//! ```
+
+#[macro_use]
+extern crate serde;
+extern crate serde_json;
+extern crate im_rc;
+#[cfg(test)]
+#[macro_use]
+extern crate serde_derive;
+
+use std::path::Path;
+
+pub mod symbol;
+mod tokenizer;
+mod parser;
+mod builtins;
+mod files;
+mod system;
+mod json;
+mod lists;
+mod maps;
+mod sets;
+mod atoms;
+mod errors;
+mod macros;
+mod params;
+mod modules;
+mod convert;
+pub mod collections;
+mod analyzer;
+mod compiler;
+mod vm;
+mod interpreter;
+pub mod ser;
+pub mod de;
+pub mod config;
+#[cfg(test)]
+mod testing;
+
+pub use interpreter::{Interpreter, Backend, Value, EvalResult, Builtin, Builtins};
+pub use errors::Error;
+pub use parser::is_incomplete;
+pub use tokenizer::is_symbolchar;
+pub use symbol::Symbol;
+pub use im_rc::Vector;
+pub use ser::to_value;
+pub use de::from_value;
+
+/// Evaluates `source` in a new interpreter and returns the value of the
+/// last expression.
+pub fn eval_str(source: &str) -> EvalResult {
+	Interpreter::new().eval_str(source)
+}
+
+/// Evaluates the file in a new interpreter and returns the value of the
+/// last expression.
+pub fn eval_file<P: AsRef<Path>>(path: P) -> EvalResult {
+	Interpreter::new().eval_file(path)
+}
@@ -0,0 +1,177 @@
+use std::rc::Rc;
+
+use im_rc::Vector;
+
+use interpreter::{Value, EvalResult, Builtins};
+use maps;
+
+// Lists are persistent vectors: the operations below return new lists that
+// share most of their structure with the old one, indexing, `conj`, `assoc`
+// and slicing take O(log n).
+pub const BUILTINS: &Builtins = &[
+	("list", list, "(list x ...) Returns a list of all arguments."),
+	("count", count, "(count coll) The number of items of a list, map or set, the length of a string."),
+	("nth", nth, "(nth list i) The item at index i, errors if i is out of bounds."),
+	("first", first, "(first list) The first item of list, nil if it is empty."),
+	("rest", rest, "(rest list) All items of list but the first."),
+	("conj", conj, "(conj list x ...) Returns list with x ... appended, (conj set x ...) set with x ... added."),
+	("assoc", assoc, "(assoc list i x) Returns list with the item at index i replaced by x, (assoc map key x) map with key set to x."),
+	("slice", slice, "(slice list start end) The items from start up to end (exclusive), end defaults to the length."),
+	("concat", concat, "(concat list ...) Returns all lists joined into one.")
+];
+
+fn list_arg<'a>(name: &str, value: &'a Value) -> Result<&'a Vector<Rc<Value>>, String> {
+	match value {
+		Value::List(list) => Ok(list),
+		_ => Err(format!("{} takes a list, got {}", name, value.type_name()))
+	}
+}
+
+// an index into list, `end` allows the index right after the last item
+fn index_arg(name: &str, value: &Value, list: &Vector<Rc<Value>>, end: bool) -> Result<usize, String> {
+	let index = match value {
+		&Value::Int(i) => i,
+		_ => { return Err(format!("{} takes an int as index, got {}", name, value.type_name())); }
+	};
+	let len = list.len() as i64;
+	if index < 0 || index > len || (index == len && !end) {
+		return Err(format!("{}: index {} out of bounds for a list of length {}", name, index, len));
+	}
+	Ok(index as usize)
+}
+
+pub fn list(args: Vec<Rc<Value>>) -> EvalResult {
+	Ok(Rc::new(Value::List(args.into_iter().collect())))
+}
+
+pub fn count(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 1 {
+		return Err("count takes only one argument".into());
+	}
+
+	let len = match *args[0] {
+		Value::List(ref list) => list.len(),
+		Value::Map(ref map) => map.len(),
+		Value::Set(ref set) => set.len(),
+		Value::Str(ref s) => s.chars().count(),
+		ref value => { return Err(format!("count takes a list, map, set or string, got {}", value.type_name()).into()); }
+	};
+	Ok(Rc::new(Value::Int(len as i64)))
+}
+
+pub fn nth(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 2 {
+		return Err("nth takes a list and an index".into());
+	}
+
+	let list = list_arg("nth", &args[0])?;
+	let index = index_arg("nth", &args[1], list, false)?;
+	Ok(list[index].clone())
+}
+
+pub fn first(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 1 {
+		return Err("first takes only one argument of type list".into());
+	}
+
+	match list_arg("first", &args[0])?.front() {
+		Some(value) => Ok(value.clone()),
+		None => Ok(Rc::new(Value::Nil))
+	}
+}
+
+pub fn rest(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 1 {
+		return Err("rest takes only one argument of type list".into());
+	}
+
+	let list = list_arg("rest", &args[0])?;
+	if list.is_empty() {
+		return Ok(args[0].clone());
+	}
+	Ok(Rc::new(Value::List(list.skip(1))))
+}
+
+pub fn conj(args: Vec<Rc<Value>>) -> EvalResult {
+	let mut iter = args.into_iter();
+	let mut list = match iter.next() {
+		Some(value) => match *value {
+			Value::Set(ref set) => {
+				let mut set = set.clone();
+				set.extend(iter);
+				return Ok(Rc::new(Value::Set(set)));
+			},
+			_ => list_arg("conj", &value)?.clone()
+		},
+		None => { return Err("conj takes a list and items to append".into()); }
+	};
+	list.extend(iter);
+	Ok(Rc::new(Value::List(list)))
+}
+
+pub fn assoc(args: Vec<Rc<Value>>) -> EvalResult {
+	if let Some(&Value::Map(_)) = args.first().map(|arg| &**arg) {
+		return maps::assoc(args);
+	}
+	if args.len() != 3 {
+		return Err("assoc takes a list, an index and a value".into());
+	}
+
+	let list = list_arg("assoc", &args[0])?;
+	let index = index_arg("assoc", &args[1], list, false)?;
+	Ok(Rc::new(Value::List(list.update(index, args[2].clone()))))
+}
+
+pub fn slice(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 2 && args.len() != 3 {
+		return Err("slice takes a list, a start and an optional end index".into());
+	}
+
+	let list = list_arg("slice", &args[0])?;
+	let start = index_arg("slice", &args[1], list, true)?;
+	let end = match args.get(2) {
+		Some(end) => index_arg("slice", end, list, true)?,
+		None => list.len()
+	};
+	if end < start {
+		return Err(format!("slice: end {} is before start {}", end, start).into());
+	}
+	Ok(Rc::new(Value::List(list.clone().slice(start..end))))
+}
+
+pub fn concat(args: Vec<Rc<Value>>) -> EvalResult {
+	let mut res: Vector<Rc<Value>> = Vector::new();
+	for arg in &args {
+		res.append(list_arg("concat", arg)?.clone());
+	}
+	Ok(Rc::new(Value::List(res)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use testing::{eval, int, ints};
+
+	#[test]
+	fn lists() {
+		assert_eq!(eval("(conj { 1 2 } 3 4)"), ints(&[1, 2, 3, 4]));
+		assert_eq!(eval("(nth { 1 2 3 } 1)"), int(2));
+		assert_eq!(eval("(nth { 1 2 3 } 3)"), Err("nth: index 3 out of bounds for a list of length 3".into()));
+		assert_eq!(eval("(assoc { 1 2 3 } 0 9)"), ints(&[9, 2, 3]));
+		assert_eq!(eval("(slice { 1 2 3 4 } 1 3)"), ints(&[2, 3]));
+		assert_eq!(eval("(slice { 1 2 3 4 } 4)"), ints(&[]));
+		assert_eq!(eval("(slice { 1 2 3 4 } 3 1)"), Err("slice: end 1 is before start 3".into()));
+		assert_eq!(eval("(concat { 1 } {} { 2 3 })"), ints(&[1, 2, 3]));
+		assert_eq!(eval("(list (first { 1 2 }) (count (rest { 1 2 })) (count \"äb\"))"), ints(&[1, 1, 2]));
+		assert_eq!(eval("(first {})"), Ok(Rc::new(Value::Nil)));
+		assert_eq!(eval("(nth \"abc\" 0)"), Err("nth takes a list, got string".into()));
+	}
+
+	#[test]
+	fn sharing() {
+		// the old list is unchanged
+		assert_eq!(eval("(def a (list 1 2 3)) (def b (assoc (conj a 4) 0 0)) { a b }"), Ok(Rc::new(Value::List(
+			vec![ints(&[1, 2, 3]).unwrap(), ints(&[0, 2, 3, 4]).unwrap()].into_iter().collect()
+		))));
+	}
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use im_rc::Vector;
+
+use parser::Node;
+use symbol::{self, Symbol};
+
+// Macros are templates: expanding one substitutes every parameter symbol in
+// the template with the (unevaluated) argument node. `& rest` inside of the
+// template splices all remaining arguments.
+#[derive(Debug, Clone)]
+pub struct Macro {
+	pub clauses: Vec<Clause>,
+	pub doc: Option<String>
+}
+
+#[derive(Debug, Clone)]
+pub struct Clause {
+	params: Vec<Symbol>,
+	rest: Option<Symbol>,
+	template: Rc<Node>
+}
+
+impl Clause {
+	pub fn new(params: &Vector<Rc<Node>>, template: Rc<Node>) -> Result<Clause, String> {
+		let mut names: Vec<Symbol> = vec![];
+		let mut rest: Option<Symbol> = None;
+		let mut iter = params.iter();
+		while let Some(node) = iter.next() {
+			match **node {
+				Node::Symbol(symbol::AMPERSAND) => {
+					match iter.next().map(|node| &**node) {
+						Some(&Node::Symbol(sym)) => { rest = Some(sym); },
+						_ => { return Err("illegal defmacro syntax".to_string()); }
+					}
+					if iter.next().is_some() {
+						return Err("illegal defmacro syntax".to_string());
+					}
+				},
+				Node::Symbol(sym) => names.push(sym),
+				_ => { return Err("illegal defmacro syntax".to_string()); }
+			}
+		}
+
+		Ok(Clause {
+			params: names,
+			rest,
+			template
+		})
+	}
+
+	pub fn signature(&self) -> String {
+		let mut params: Vec<&str> = self.params.iter().map(|param| param.as_str()).collect();
+		if let Some(rest) = self.rest {
+			params.push("&");
+			params.push(rest.as_str());
+		}
+		params.join(" ")
+	}
+
+	fn accepts(&self, argc: usize) -> bool {
+		argc == self.params.len() || (self.rest.is_some() && argc > self.params.len())
+	}
+}
+
+pub fn expand(clauses: &[Clause], args: Vec<Rc<Node>>) -> Result<Rc<Node>, String> {
+	let clause = match clauses.iter().find(|clause| clause.accepts(args.len())) {
+		Some(clause) => clause,
+		None => { return Err(format!("no macro clause takes {} arguments", args.len())); }
+	};
+
+	let mut bindings: HashMap<Symbol, Rc<Node>> = HashMap::new();
+	let mut iter = args.into_iter();
+	for &param in &clause.params {
+		bindings.insert(param, iter.next().unwrap());
+	}
+	let rest: Vec<Rc<Node>> = iter.collect();
+
+	let expander = Expander {
+		bindings,
+		rest: clause.rest.map(|name| (name, rest))
+	};
+	Ok(expander.substitute(&clause.template))
+}
+
+struct Expander {
+	bindings: HashMap<Symbol, Rc<Node>>,
+	rest: Option<(Symbol, Vec<Rc<Node>>)>
+}
+
+impl Expander {
+	fn substitute(&self, node: &Rc<Node>) -> Rc<Node> {
+		match **node {
+			Node::Symbol(sym) => {
+				match self.bindings.get(&sym) {
+					Some(arg) => arg.clone(),
+					None => node.clone()
+				}
+			},
+			Node::Expr(ref items) => Rc::new(Node::Expr(self.substitute_all(items))),
+			Node::List(ref items) => Rc::new(Node::List(self.substitute_all(items))),
+			Node::Set(ref items) => Rc::new(Node::Set(self.substitute_all(items))),
+			Node::Map(ref map) => {
+				let map = map.iter().map(|(key, node)| (key.clone(), self.substitute(node))).collect();
+				Rc::new(Node::Map(map))
+			},
+			_ => node.clone()
+		}
+	}
+
+	fn substitute_all(&self, items: &Vector<Rc<Node>>) -> Vector<Rc<Node>> {
+		let mut res: Vector<Rc<Node>> = Vector::new();
+		let mut iter = items.iter().peekable();
+		while let Some(item) = iter.next() {
+			if let Some(&(name, ref rest)) = self.rest.as_ref() {
+				let splice = match (&**item, iter.peek().map(|next| &***next)) {
+					(&Node::Symbol(symbol::AMPERSAND), Some(&Node::Symbol(sym))) => sym == name,
+					_ => false
+				};
+				if splice {
+					iter.next().unwrap();
+					res.extend(rest.iter().cloned());
+					continue;
+				}
+			}
+			res.push_back(self.substitute(item));
+		}
+		res
+	}
+}
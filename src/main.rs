@@ -1,15 +1,16 @@
-use std::io::Write;
+// Kept in the style of the original command line interface.
+#![allow(clippy::needless_return, clippy::match_ref_pats, clippy::explicit_counter_loop,
+	clippy::redundant_field_names)]
+
+extern crate lial;
+extern crate rustyline;
+
+use std::io::{Read, Write};
 use std::io;
-use std::io::prelude::*;
 use std::rc::Rc;
 use std::env;
-use std::fs::File;
-
-mod tokenizer;
-mod parser;
-mod builtins;
-mod interpreter;
 
+use lial::{Interpreter, Backend, Value};
 
 macro_rules! perror(
 	($($arg:tt)*) => { {
@@ -17,111 +18,124 @@ macro_rules! perror(
 	} }
 );
 
-fn process_line(interpreter: &mut interpreter::Interpreter, line: &String) -> Result<Vec<Rc<interpreter::Value>>, String> {
-	match tokenizer::Tokenizer::new(&line).collect() {
-		Ok(tokens) => {
-			match parser::Parser::new(tokens).collect() {
-				Ok(nodes) => {
-					let nodes: Vec<parser::Node> = nodes;
-					let mut values: Vec<Rc<interpreter::Value>> = vec![];
-					for node in nodes {
-						match interpreter.eval(Rc::new(node)) {
-							Ok(value) => values.push(value),
-							Err(e) => { return Err(e); }
-						}
-					}
-					return Ok(values);
-				},
-				Err(e) => Err(e)
-			}
-		},
-		Err(e) => Err(e)
+mod repl;
+
+#[derive(Clone, Copy)]
+struct Options {
+	prelude: bool,
+	backend: Backend
+}
+
+fn new_interpreter(options: Options) -> Interpreter {
+	if options.prelude {
+		Interpreter::with_backend(options.backend)
+	} else {
+		let mut interpreter = Interpreter::without_prelude();
+		interpreter.set_backend(options.backend);
+		interpreter
 	}
 }
 
-fn process_file(filename: String) {
-	let mut interpreter = interpreter::Interpreter::new();
-	let mut file = match File::open(filename) {
-		Ok(file) => file,
-		Err(e) => {
-			perror!("{:?}", e);
-			std::process::exit(1);
-		}
-	};
+const USAGE: &str = "\
+usage: lial [options] [<file> [args...]]
+       lial [options] run <file> [args...]
+       lial [options] -e <program> [args...]
+       lial [options] - [args...]
+       lial [options] repl
 
-	let mut buf = String::new();
-	match file.read_to_string(&mut buf) {
-		Ok(_) => {},
-		Err(e) => {
-			perror!("{:?}", e);
-			std::process::exit(1);
-		}
-	};
+options:
+  --no-prelude   start without the prelude
+  --vm           compile to bytecode instead of walking the syntax tree
+  -h, --help     print this help
+  -V, --version  print the version";
 
-	match tokenizer::Tokenizer::new(&buf).collect() {
-		Ok(tokens) => {
-			match parser::Parser::new(tokens).collect() {
-				Ok(nodes) => {
-					let nodes: Vec<parser::Node> = nodes;
-					for node in nodes {
-						match interpreter.eval(Rc::new(node)) {
-							Ok(_) => {},
-							Err(e) => {
-								perror!("Error: {:?}", e);
-								std::process::exit(1);
-							}
-						}
-					}
-				},
-				Err(e) => {
-					perror!("Error: {:?}", e);
-					std::process::exit(1);
-				}
-			}
-		},
-		Err(e) => {
-			perror!("Error: {:?}", e);
-			std::process::exit(1);
-		}
+enum Command {
+	Run(String, Vec<String>),
+	Eval(String, Vec<String>),
+	Stdin(Vec<String>),
+	Repl,
+	Help,
+	Version
+}
+
+fn parse_args<I: Iterator<Item=String>>(mut args: I) -> Result<(Command, Options), String> {
+	let mut options = Options { prelude: true, backend: Backend::TreeWalker };
+	while let Some(arg) = args.next() {
+		let command = match arg.as_str() {
+			"--no-prelude" => {
+				options.prelude = false;
+				continue;
+			},
+			"--vm" => {
+				options.backend = Backend::Vm;
+				continue;
+			},
+			"-h" | "--help" => Command::Help,
+			"-V" | "--version" => Command::Version,
+			"-e" => match args.next() {
+				Some(program) => Command::Eval(program, args.collect()),
+				None => { return Err("-e expects a program".to_string()); }
+			},
+			"-" => Command::Stdin(args.collect()),
+			"repl" => Command::Repl,
+			"run" => match args.next() {
+				Some(filename) => Command::Run(filename, args.collect()),
+				None => { return Err("run expects a file".to_string()); }
+			},
+			_ if arg.starts_with('-') => { return Err(format!("unknown option `{}`", arg)); },
+			_ => Command::Run(arg, args.collect())
+		};
+		return Ok((command, options));
 	}
+	Ok((Command::Repl, options))
+}
 
+// everything after the program is passed to the script as `*args*`
+fn script_interpreter(args: Vec<String>, options: Options) -> Interpreter {
+	let mut interpreter = new_interpreter(options);
+	let args = args.into_iter().map(|arg| Rc::new(Value::Str(arg))).collect();
+	interpreter.define("*args*", Rc::new(Value::List(args)));
+	interpreter
 }
 
-fn repl() {
-	println!("yial: REPL (Ctrl+D to exit)");
-	let mut interpreter = interpreter::Interpreter::new();
-	loop {
-		print!(">_ ");
-		io::stdout().flush().unwrap();
-		let mut buf = String::new();
-		match io::stdin().read_line(&mut buf) {
-			Ok(len) => {
-				if len == 0 { break; }
-
-				match process_line(&mut interpreter, &buf) {
-					Ok(values) => {
-						let mut i = 0;
-						for value in values {
-							perror!("${} = {:?}", i, value);
-							i += 1;
-						}
-					},
-					Err(e) => perror!("Error: {:?}", e)
-				}
-			},
-			Err(e) => {
-				perror!("Error: {:?}", e);
-				std::process::exit(1);
-			}
-		}
+fn process_file(filename: String, args: Vec<String>, options: Options) {
+	let mut interpreter = script_interpreter(args, options);
+	if let Err(e) = interpreter.eval_file(&filename) {
+		perror!("Error: {:?}", e.message());
+		std::process::exit(1);
+	}
+}
+
+fn process_source(source: String, args: Vec<String>, options: Options) {
+	let mut interpreter = script_interpreter(args, options);
+	if let Err(e) = interpreter.eval_all(&source) {
+		perror!("Error: {:?}", e.message());
+		std::process::exit(1);
 	}
-	println!("\nBye!");
 }
 
 fn main() {
-	if let Some(filename) = env::args().nth(1) {
-		process_file(filename);
-	} else {
-		repl();
+	let (command, options) = match parse_args(env::args().skip(1)) {
+		Ok(res) => res,
+		Err(e) => {
+			perror!("lial: {}\n{}", e, USAGE);
+			std::process::exit(2);
+		}
+	};
+
+	match command {
+		Command::Run(filename, args) => process_file(filename, args, options),
+		Command::Eval(program, args) => process_source(program, args, options),
+		Command::Stdin(args) => {
+			let mut buf = String::new();
+			if let Err(e) = io::stdin().read_to_string(&mut buf) {
+				perror!("Error: {:?}", e);
+				std::process::exit(1);
+			}
+			process_source(buf, args, options);
+		},
+		Command::Repl => repl::run(|| new_interpreter(options)),
+		Command::Help => println!("{}", USAGE),
+		Command::Version => println!("lial {}", env!("CARGO_PKG_VERSION"))
 	}
 }
@@ -0,0 +1,123 @@
+use std::rc::Rc;
+
+use interpreter::{Value, EvalResult, Builtins};
+use collections::{self, Map};
+
+// Maps are persistent (see `collections`), `assoc` (in `lists`) and `dissoc`
+// return new maps and leave the old ones untouched.
+pub const BUILTINS: &Builtins = &[
+	("get", get, "(get map key default) The value of key (a string or keyword) in map, default (or nil) if there is none."),
+	("dissoc", dissoc, "(dissoc map key ...) Returns map without the keys."),
+	("keys", keys, "(keys map) A list of the keys of map, sorted."),
+	("vals", vals, "(vals map) A list of the values of map, in the order of their keys."),
+	("merge", merge, "(merge map ...) Returns all maps merged into one, later maps win.")
+];
+
+fn map_arg<'a>(name: &str, value: &'a Value) -> Result<&'a Map<String, Rc<Value>>, String> {
+	match value {
+		Value::Map(map) => Ok(map),
+		_ => Err(format!("{} takes a map, got {}", name, value.type_name()))
+	}
+}
+
+// `:name` and "name" are the same key
+fn key_arg(name: &str, value: &Value) -> Result<String, String> {
+	match value {
+		&Value::Str(ref key) | &Value::Keyword(ref key) => Ok(key.clone()),
+		_ => Err(format!("{} takes a string or keyword as key, got {}", name, value.type_name()))
+	}
+}
+
+pub fn get(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 2 && args.len() != 3 {
+		return Err("get takes a map, a key and an optional default".into());
+	}
+
+	let map = map_arg("get", &args[0])?;
+	match map.get(&key_arg("get", &args[1])?) {
+		Some(value) => Ok(value.clone()),
+		None => Ok(args.get(2).cloned().unwrap_or_else(|| Rc::new(Value::Nil)))
+	}
+}
+
+// (assoc map key x), called by `lists::assoc`
+pub fn assoc(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 3 {
+		return Err("assoc takes a map, a key and a value".into());
+	}
+
+	let map = map_arg("assoc", &args[0])?;
+	let key = key_arg("assoc", &args[1])?;
+	Ok(Rc::new(Value::Map(map.update(key, args[2].clone()))))
+}
+
+pub fn dissoc(args: Vec<Rc<Value>>) -> EvalResult {
+	let mut iter = args.iter();
+	let mut map = match iter.next() {
+		Some(value) => map_arg("dissoc", value)?.clone(),
+		None => { return Err("dissoc takes a map and keys to remove".into()); }
+	};
+	for key in iter {
+		map.remove(&key_arg("dissoc", key)?);
+	}
+	Ok(Rc::new(Value::Map(map)))
+}
+
+pub fn keys(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 1 {
+		return Err("keys takes only one argument of type map".into());
+	}
+
+	let map = map_arg("keys", &args[0])?;
+	Ok(Rc::new(Value::List(collections::sorted(map).into_iter().map(|(key, _)| Rc::new(Value::Str(key.clone()))).collect())))
+}
+
+pub fn vals(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 1 {
+		return Err("vals takes only one argument of type map".into());
+	}
+
+	let map = map_arg("vals", &args[0])?;
+	Ok(Rc::new(Value::List(collections::sorted(map).into_iter().map(|(_, value)| value.clone()).collect())))
+}
+
+pub fn merge(args: Vec<Rc<Value>>) -> EvalResult {
+	let mut res: Map<String, Rc<Value>> = Map::default();
+	for arg in &args {
+		for (key, value) in map_arg("merge", arg)? {
+			res.insert(key.clone(), value.clone());
+		}
+	}
+	Ok(Rc::new(Value::Map(res)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use testing::{eval, printed};
+
+	#[test]
+	fn maps() {
+		assert_eq!(eval("(get { a: 1 } :a)"), Ok(Rc::new(Value::Int(1))));
+		assert_eq!(eval("(get { a: 1 } \"b\" 2)"), Ok(Rc::new(Value::Int(2))));
+		assert_eq!(eval("(get { a: 1 } 1)"), Err("get takes a string or keyword as key, got int".into()));
+		assert_eq!(eval("(= (assoc { a: 1 } :b 2) { a: 1 b: 2 })"), Ok(Rc::new(Value::Bool(true))));
+		assert_eq!(eval("(= (dissoc { a: 1 b: 2 c: 3 } :a \"c\") { b: 2 })"), Ok(Rc::new(Value::Bool(true))));
+		assert_eq!(eval("(= (merge { a: 1 b: 2 } { b: 3 }) { a: 1 b: 3 })"), Ok(Rc::new(Value::Bool(true))));
+		assert_eq!(eval("(count (keys { a: 1 b: 2 }))"), Ok(Rc::new(Value::Int(2))));
+
+		// the old map is unchanged
+		assert_eq!(eval("(def m { a: 1 }) (assoc m :a 2) (dissoc m :a) m"), eval("{ a: 1 }"));
+	}
+
+	#[test]
+	fn sorted() {
+		// maps print and iterate sorted by key, however they were built
+		let expected = "{ a: 1 b: 2 c: 3 d: 4 e: 5 f: 6 g: 7 }";
+		assert_eq!(printed("{ g: 7 a: 1 f: 6 b: 2 e: 5 c: 3 d: 4 }"), expected);
+		assert_eq!(printed("(assoc (merge { g: 7 f: 6 e: 5 } { d: 4 c: 3 b: 2 }) :a 1)"), expected);
+		assert_eq!(printed("(keys { z: 1 y: 2 x: 3 })"), "{ x y z }");
+		assert_eq!(printed("(vals { z: 1 y: 2 x: 3 })"), "{ 3 2 1 }");
+		assert_eq!(printed("{ b: { d: 1 c: 2 } a: {:} }"), "{ a: {:} b: { c: 2 d: 1 } }");
+	}
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::env;
+
+pub const EXTENSION: &str = "lial";
+
+/// Keeps track of imported files: where to find them, which ones are
+/// already loaded (and under which namespace) and which ones are
+/// currently being loaded (to detect cycles).
+pub struct Modules {
+	search_path: Vec<PathBuf>,
+	loaded: HashMap<PathBuf, Rc<String>>,
+	loading: Vec<PathBuf>,
+	aliases: HashMap<Option<Rc<String>>, HashMap<String, Rc<String>>>
+}
+
+impl Modules {
+	pub fn new() -> Modules {
+		let search_path = match env::var_os("LIAL_PATH") {
+			Some(paths) => env::split_paths(&paths).collect(),
+			None => vec![]
+		};
+
+		Modules {
+			search_path,
+			loaded: HashMap::new(),
+			loading: vec![],
+			aliases: HashMap::new()
+		}
+	}
+
+	/// `(import math)` looks for `math.lial`, `(import "lib/math.lial")`
+	/// for the given path. Relative paths are tried relative to the
+	/// directory of the importing file (or the working directory) first,
+	/// then relative to every directory in `LIAL_PATH`.
+	pub fn resolve(&self, target: &str) -> Result<PathBuf, String> {
+		let target = Path::new(target);
+		let mut candidates: Vec<PathBuf> = vec![];
+		if target.is_absolute() {
+			candidates.push(target.to_path_buf());
+		} else {
+			match self.loading.last().and_then(|file| file.parent()) {
+				Some(dir) => candidates.push(dir.join(target)),
+				None => candidates.push(target.to_path_buf())
+			}
+			for dir in &self.search_path {
+				candidates.push(dir.join(target));
+			}
+		}
+
+		for candidate in candidates {
+			if candidate.is_file() {
+				return match candidate.canonicalize() {
+					Ok(path) => Ok(path),
+					Err(e) => Err(format!("{}: {}", candidate.display(), e))
+				};
+			}
+		}
+		Err(format!("module `{}` not found", target.display()))
+	}
+
+	pub fn namespace(&self, path: &Path) -> Option<Rc<String>> {
+		self.loaded.get(path).cloned()
+	}
+
+	pub fn enter(&mut self, path: &Path) -> Result<(), String> {
+		if let Some(pos) = self.loading.iter().position(|file| file == path) {
+			let mut chain: Vec<String> = self.loading[pos..].iter()
+				.map(|file| file.display().to_string()).collect();
+			chain.push(path.display().to_string());
+			return Err(format!("import cycle: {}", chain.join(" -> ")));
+		}
+		self.loading.push(path.to_path_buf());
+		Ok(())
+	}
+
+	pub fn exit(&mut self) {
+		self.loading.pop().expect("no file is being loaded");
+	}
+
+	/// Errors if another file already uses the namespace ns.
+	pub fn check_namespace(&self, path: &Path, ns: &Rc<String>) -> Result<(), String> {
+		for (other, other_ns) in &self.loaded {
+			if other_ns == ns && other != path {
+				return Err(format!("namespace `{}` already loaded from {}", ns, other.display()));
+			}
+		}
+		Ok(())
+	}
+
+	/// Marks path as loaded, once it has been evaluated.
+	pub fn register(&mut self, path: &Path, ns: Rc<String>) {
+		self.loaded.insert(path.to_path_buf(), ns);
+	}
+
+	pub fn alias(&mut self, ns: Option<Rc<String>>, alias: String, target: Rc<String>) {
+		self.aliases.entry(ns).or_default().insert(alias, target);
+	}
+
+	/// Rewrites `alias/name` to `namespace/name` if `alias` was defined
+	/// with `:as` inside of `ns`.
+	pub fn resolve_alias(&self, ns: &Option<Rc<String>>, symbol: &str) -> Option<String> {
+		let pos = match symbol.find('/') {
+			Some(pos) if pos > 0 => pos,
+			_ => { return None; }
+		};
+
+		self.aliases.get(ns)
+			.and_then(|aliases| aliases.get(&symbol[..pos]))
+			.map(|target| format!("{}{}", target, &symbol[pos..]))
+	}
+}
+
+/// The namespace of a module is the name of its file without extension.
+pub fn namespace_of(path: &Path) -> Result<Rc<String>, String> {
+	match path.file_stem().and_then(|stem| stem.to_str()) {
+		Some(stem) => Ok(Rc::new(stem.to_string())),
+		None => Err(format!("cannot derive namespace from {}", path.display()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn aliases() {
+		let mut modules = Modules::new();
+		let root: Option<Rc<String>> = None;
+		modules.alias(root.clone(), "m".to_string(), Rc::new("math".to_string()));
+		assert_eq!(modules.resolve_alias(&root, "m/square"), Some("math/square".to_string()));
+		assert_eq!(modules.resolve_alias(&root, "x/square"), None);
+		assert_eq!(modules.resolve_alias(&root, "/"), None);
+		assert_eq!(modules.resolve_alias(&Some(Rc::new("util".to_string())), "m/square"), None);
+	}
+
+	#[test]
+	fn cycles() {
+		let mut modules = Modules::new();
+		assert!(modules.enter(Path::new("/a.lial")).is_ok());
+		assert!(modules.enter(Path::new("/b.lial")).is_ok());
+		assert_eq!(modules.enter(Path::new("/a.lial")),
+			Err("import cycle: /a.lial -> /b.lial -> /a.lial".to_string()));
+		modules.exit();
+		modules.exit();
+		assert!(modules.enter(Path::new("/a.lial")).is_ok());
+	}
+}
@@ -0,0 +1,554 @@
+use std::fmt;
+use std::rc::Rc;
+
+use im_rc::Vector;
+
+use parser::Node;
+use interpreter::Value;
+use symbol::{self, Symbol};
+
+// The parameters of one arity of a function: `{a b (c default) & more}`.
+// Every parameter is a local variable, in this order: the required ones,
+// the optional ones (their default is evaluated in the scope of the function
+// when the argument is missing) and the rest list. A parameter can also be a
+// pattern, its variables follow the parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Params {
+	pub names: Vec<Symbol>,
+	pub required: usize,
+	pub defaults: Vec<Rc<Node>>,
+	pub rest: bool,
+	// the parameters that are patterns, by slot
+	pub patterns: Vec<(usize, Pattern)>
+}
+
+// A destructuring pattern: a name, `_` (matches anything), a literal (matches
+// equal values), a list pattern `{first second & rest}` or a map pattern
+// `{ name: n age: a }`. Patterns nest.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+	Name(Symbol),
+	Wildcard,
+	Literal(Rc<Value>),
+	List(Vec<Pattern>, Option<Box<Pattern>>),
+	// sorted by key
+	Map(Vec<(String, Pattern)>)
+}
+
+// A `fn` form: its documentation and the parameters and body of every arity,
+// either `(fn {params} "doc" body...)` or `(fn "doc" ({params} body...) ...)`.
+pub struct Arities {
+	pub doc: Option<String>,
+	pub arities: Vec<(Params, Vec<Rc<Node>>)>
+}
+
+impl Params {
+	pub fn none() -> Params {
+		Params { names: vec![], required: 0, defaults: vec![], rest: false, patterns: vec![] }
+	}
+
+	pub fn parse(list: &Vector<Rc<Node>>) -> Result<Params, String> {
+		let mut params = Params::none();
+		let mut iter = list.iter();
+		while let Some(node) = iter.next() {
+			match **node {
+				Node::Symbol(symbol::AMPERSAND) => {
+					match (iter.next(), iter.next()) {
+						(Some(param), None) => params.push(param)?,
+						_ => { return Err("illegal fn syntax: `&` takes exactly one rest parameter".to_string()); }
+					}
+					params.rest = true;
+				},
+				Node::Expr(ref items) if items.len() == 2 => {
+					params.push(&items[0])?;
+					params.defaults.push(items[1].clone());
+				},
+				_ => {
+					if !params.defaults.is_empty() {
+						return Err(format!("illegal fn syntax: required parameter `{}` after optional ones", Pattern::parse(node)?));
+					}
+					params.push(node)?;
+					params.required += 1;
+				}
+			}
+		}
+
+		let mut names: Vec<Symbol> = vec![];
+		for (_, pattern) in &params.patterns {
+			pattern.names(&mut names);
+		}
+		params.names.extend(names);
+		Ok(params)
+	}
+
+	fn push(&mut self, node: &Rc<Node>) -> Result<(), String> {
+		match Pattern::parse(node)? {
+			Pattern::Name(name) => self.names.push(name),
+			pattern => {
+				// the argument itself cannot be referred to
+				self.patterns.push((self.names.len(), pattern));
+				self.names.push(Symbol::intern(" "));
+			}
+		}
+		Ok(())
+	}
+
+	// the number of parameters, without the variables of patterns
+	pub fn slots(&self) -> usize {
+		self.required + self.defaults.len() + if self.rest { 1 } else { 0 }
+	}
+
+	// the parameter (or pattern) in slot
+	fn param(&self, slot: usize) -> Node {
+		match self.patterns.iter().find(|&&(at, _)| at == slot) {
+			Some((_, pattern)) => pattern.to_node(),
+			None => Node::Symbol(self.names[slot])
+		}
+	}
+
+	// the parameter list, as written in a `fn`
+	pub fn to_node(&self) -> Node {
+		let mut list: Vector<Rc<Node>> = Vector::new();
+		for i in 0..self.slots() {
+			if i >= self.required && i < self.required + self.defaults.len() {
+				let default = vec![Rc::new(self.param(i)), self.defaults[i - self.required].clone()];
+				list.push_back(Rc::new(Node::Expr(default.into_iter().collect())));
+				continue;
+			}
+			if self.rest && i == self.slots() - 1 {
+				list.push_back(Rc::new(Node::Symbol(symbol::AMPERSAND)));
+			}
+			list.push_back(Rc::new(self.param(i)));
+		}
+		Node::List(list)
+	}
+
+	pub fn accepts(&self, argc: usize) -> bool {
+		argc >= self.required && (self.rest || argc <= self.required + self.defaults.len())
+	}
+
+	// The values of the parameters: the arguments, nil for missing optional
+	// ones, the rest list and nil for the variables of patterns. Only for
+	// accepted arguments.
+	pub fn bind(&self, mut args: Vec<Rc<Value>>) -> Vec<Rc<Value>> {
+		if self.is_simple() {
+			return args;
+		}
+		let positional = self.required + self.defaults.len();
+		let rest: Vector<Rc<Value>> = if args.len() > positional {
+			args.drain(positional..).collect()
+		} else {
+			Vector::new()
+		};
+		while args.len() < positional {
+			args.push(Rc::new(Value::Nil));
+		}
+		if self.rest {
+			args.push(Rc::new(Value::List(rest)));
+		}
+		while args.len() < self.names.len() {
+			args.push(Rc::new(Value::Nil));
+		}
+		args
+	}
+
+	// Whether `bind` changes the arguments at all.
+	pub fn is_simple(&self) -> bool {
+		self.defaults.is_empty() && !self.rest && self.patterns.is_empty()
+	}
+
+	// The values of the variables of the patterns, given the values of the
+	// parameters.
+	pub fn destructure(&self, values: &[Rc<Value>]) -> Result<Vec<Rc<Value>>, String> {
+		let mut res: Vec<Rc<Value>> = vec![];
+		for &(slot, ref pattern) in &self.patterns {
+			pattern.bind(&values[slot], &mut res)?;
+		}
+		Ok(res)
+	}
+
+	// "1", "1 to 3" or "at least 2"
+	fn count(&self) -> String {
+		if self.rest {
+			format!("at least {}", self.required)
+		} else if !self.defaults.is_empty() {
+			format!("{} to {}", self.required, self.required + self.defaults.len())
+		} else {
+			self.required.to_string()
+		}
+	}
+
+	// Whether the values of the parameters have the shape of the patterns.
+	pub fn matches(&self, values: &[Rc<Value>]) -> bool {
+		self.patterns.iter().all(|&(slot, ref pattern)| pattern.matches(&values[slot]))
+	}
+
+	// `a b (c ...) & more`, for the documentation
+	pub fn signature(&self) -> String {
+		let mut params: Vec<String> = vec![];
+		for i in 0..self.slots() {
+			let param = match self.patterns.iter().find(|&&(at, _)| at == i) {
+				Some((_, pattern)) => pattern.to_string(),
+				None => self.names[i].to_string()
+			};
+			if i >= self.required && i < self.required + self.defaults.len() {
+				params.push(format!("({} ...)", param));
+				continue;
+			}
+			if self.rest && i == self.slots() - 1 {
+				params.push("&".to_string());
+			}
+			params.push(param);
+		}
+		params.join(" ")
+	}
+}
+
+impl Pattern {
+	pub fn parse(node: &Node) -> Result<Pattern, String> {
+		match *node {
+			Node::Symbol(symbol::AMPERSAND) => Err("illegal pattern: misplaced `&`".to_string()),
+			Node::Symbol(name) if name == Symbol::intern("_") => Ok(Pattern::Wildcard),
+			Node::Symbol(name) => Ok(Pattern::Name(name)),
+			Node::Int(i) => Ok(Pattern::Literal(Rc::new(Value::Int(i)))),
+			Node::Real(r) => Ok(Pattern::Literal(Rc::new(Value::Real(r)))),
+			Node::Str(ref s) => Ok(Pattern::Literal(Rc::new(Value::Str(s.clone())))),
+			Node::Keyword(ref k) => Ok(Pattern::Literal(Rc::new(Value::Keyword(k.clone())))),
+			Node::Bool(b) => Ok(Pattern::Literal(Rc::new(Value::Bool(b)))),
+			Node::Nil => Ok(Pattern::Literal(Rc::new(Value::Nil))),
+			Node::List(ref items) => {
+				let mut patterns: Vec<Pattern> = vec![];
+				let mut rest: Option<Box<Pattern>> = None;
+				let mut iter = items.iter();
+				while let Some(item) = iter.next() {
+					if let Node::Symbol(symbol::AMPERSAND) = **item {
+						match (iter.next(), iter.next()) {
+							(Some(item), None) => { rest = Some(Box::new(Pattern::parse(item)?)); },
+							_ => { return Err("illegal pattern: `&` takes exactly one rest pattern".to_string()); }
+						}
+					} else {
+						patterns.push(Pattern::parse(item)?);
+					}
+				}
+				Ok(Pattern::List(patterns, rest))
+			},
+			Node::Map(ref map) => {
+				let mut entries: Vec<(String, Pattern)> = vec![];
+				for (key, node) in map {
+					entries.push((key.clone(), Pattern::parse(node)?));
+				}
+				entries.sort_by(|a, b| a.0.cmp(&b.0));
+				Ok(Pattern::Map(entries))
+			},
+			_ => Err("illegal pattern: expected a name, a literal, a list or a map".to_string())
+		}
+	}
+
+	// the variables, in the order `bind` binds them in
+	pub fn names(&self, res: &mut Vec<Symbol>) {
+		match *self {
+			Pattern::Name(name) => res.push(name),
+			Pattern::Wildcard | Pattern::Literal(_) => {},
+			Pattern::List(ref items, ref rest) => {
+				for item in items {
+					item.names(res);
+				}
+				if let Some(ref rest) = *rest {
+					rest.names(res);
+				}
+			},
+			Pattern::Map(ref entries) => {
+				for (_, pattern) in entries {
+					pattern.names(res);
+				}
+			}
+		}
+	}
+
+	pub fn to_node(&self) -> Node {
+		match *self {
+			Pattern::Name(name) => Node::Symbol(name),
+			Pattern::Wildcard => Node::Symbol(Symbol::intern("_")),
+			Pattern::Literal(ref value) => match **value {
+				Value::Int(i) => Node::Int(i),
+				Value::Real(r) => Node::Real(r),
+				Value::Str(ref s) => Node::Str(s.clone()),
+				Value::Keyword(ref k) => Node::Keyword(k.clone()),
+				Value::Bool(b) => Node::Bool(b),
+				_ => Node::Nil
+			},
+			Pattern::List(ref items, ref rest) => {
+				let mut list: Vector<Rc<Node>> = items.iter().map(|item| Rc::new(item.to_node())).collect();
+				if let Some(ref rest) = *rest {
+					list.push_back(Rc::new(Node::Symbol(symbol::AMPERSAND)));
+					list.push_back(Rc::new(rest.to_node()));
+				}
+				Node::List(list)
+			},
+			Pattern::Map(ref entries) => Node::Map(entries.iter().map(|(key, pattern)| (key.clone(), Rc::new(pattern.to_node()))).collect())
+		}
+	}
+
+	// Whether value has the shape of the pattern, `bind` without the values
+	// and error messages.
+	pub fn matches(&self, value: &Value) -> bool {
+		match (self, value) {
+			(&Pattern::Name(_), _) | (&Pattern::Wildcard, _) => true,
+			(Pattern::Literal(literal), _) => **literal == *value,
+			(Pattern::List(items, rest), Value::List(list)) => {
+				let rest = match *rest {
+					Some(ref rest) => list.len() >= items.len() && rest.matches(&Value::List(list.skip(items.len()))),
+					None => list.len() == items.len()
+				};
+				rest && items.iter().zip(list.iter()).all(|(item, value)| item.matches(value))
+			},
+			(Pattern::Map(entries), Value::Map(map)) => entries.iter().all(|(key, pattern)| match map.get(key) {
+				Some(value) => pattern.matches(value),
+				None => false
+			}),
+			_ => false
+		}
+	}
+
+	// Appends the values of the variables to res, errors if value does not
+	// have the shape of the pattern.
+	pub fn bind(&self, value: &Rc<Value>, res: &mut Vec<Rc<Value>>) -> Result<(), String> {
+		match *self {
+			Pattern::Name(_) => res.push(value.clone()),
+			Pattern::Wildcard => {},
+			Pattern::Literal(ref literal) => {
+				if *value != *literal {
+					return Err(format!("expected {}, got {}", self, value.to_string()));
+				}
+			},
+			Pattern::List(ref items, ref rest) => {
+				let list = match **value {
+					Value::List(ref list) => list,
+					_ => { return Err(format!("{} expects a list, got {}", self, value.type_name())); }
+				};
+				if list.len() < items.len() || (rest.is_none() && list.len() > items.len()) {
+					let count = if rest.is_some() { "at least " } else { "" };
+					let noun = if items.len() == 1 { "item" } else { "items" };
+					return Err(format!("{} expects a list of {}{} {}, got {}", self, count, items.len(), noun, value.to_string()));
+				}
+				for (item, value) in items.iter().zip(list.iter()) {
+					item.bind(value, res)?;
+				}
+				if let Some(ref rest) = *rest {
+					rest.bind(&Rc::new(Value::List(list.skip(items.len()))), res)?;
+				}
+			},
+			Pattern::Map(ref entries) => {
+				let map = match **value {
+					Value::Map(ref map) => map,
+					_ => { return Err(format!("{} expects a map, got {}", self, value.type_name())); }
+				};
+				for (key, pattern) in entries {
+					match map.get(key) {
+						Some(value) => pattern.bind(value, res)?,
+						None => { return Err(format!("{} expects a map with the key `{}`, got {}", self, key, value.to_string())); }
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+impl fmt::Display for Pattern {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Pattern::Name(name) => write!(f, "{}", name),
+			Pattern::Wildcard => write!(f, "_"),
+			Pattern::Literal(ref value) => match **value {
+				Value::Str(ref s) => write!(f, "{:?}", s),
+				_ => write!(f, "{}", value.to_string())
+			},
+			Pattern::List(ref items, ref rest) => {
+				let mut parts: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+				if let Some(ref rest) = *rest {
+					parts.push(format!("& {}", rest));
+				}
+				write!(f, "{{{}}}", parts.join(" "))
+			},
+			Pattern::Map(ref entries) => {
+				if entries.is_empty() {
+					return write!(f, "{{:}}");
+				}
+				let parts: Vec<String> = entries.iter().map(|(key, pattern)| format!("{}: {}", key, pattern)).collect();
+				write!(f, "{{ {} }}", parts.join(" "))
+			}
+		}
+	}
+}
+
+// The error of calling a function with `argc` arguments, none of its
+// arities accepts: "`f` takes 1, 2 or at least 4 arguments, got 3".
+pub fn arity_error<'a, I: Iterator<Item=&'a Params>>(name: Option<Symbol>, arities: I, argc: usize) -> String {
+	let mut counts: Vec<String> = arities.map(|params| params.count()).collect();
+	let plural = counts.len() > 1 || (counts[0] != "1" && counts[0] != "at least 1");
+	let last = counts.pop().unwrap();
+	let counts = if !counts.is_empty() { format!("{} or {}", counts.join(", "), last) } else { last };
+	let name = match name {
+		Some(name) => format!("`{}`", name),
+		None => "fn".to_string()
+	};
+	format!("{} takes {} argument{}, got {}", name, counts, if plural { "s" } else { "" }, argc)
+}
+
+pub fn parse_fn(args: &[Rc<Node>]) -> Result<Arities, String> {
+	match args.first().map(|node| &**node) {
+		Some(Node::List(list)) => {
+			let params = Params::parse(list)?;
+			let mut body: Vec<Rc<Node>> = args[1..].to_vec();
+			let mut doc: Option<String> = None;
+			if body.len() > 1 {
+				if let Node::Str(ref text) = *body[0] {
+					doc = Some(text.clone());
+				}
+			}
+			if doc.is_some() {
+				body.remove(0);
+			}
+			Ok(Arities { doc, arities: vec![(params, body)] })
+		},
+		Some(_) => {
+			let mut clauses = args;
+			let mut doc: Option<String> = None;
+			if let Node::Str(ref text) = *args[0] {
+				doc = Some(text.clone());
+				clauses = &args[1..];
+			}
+
+			let mut arities: Vec<(Params, Vec<Rc<Node>>)> = vec![];
+			for clause in clauses {
+				let items: Vec<Rc<Node>> = match **clause {
+					Node::Expr(ref items) => items.iter().cloned().collect(),
+					_ => { return Err("illegal fn syntax".to_string()); }
+				};
+				match items.first().map(|node| &**node) {
+					Some(Node::List(list)) => arities.push((Params::parse(list)?, items[1..].to_vec())),
+					_ => { return Err("illegal fn syntax".to_string()); }
+				}
+			}
+			if arities.is_empty() {
+				return Err("illegal fn syntax".to_string());
+			}
+			Ok(Arities { doc, arities })
+		},
+		None => Err("illegal fn syntax".to_string())
+	}
+}
+
+// The `fn` form of arities, with one clause per arity.
+pub fn to_node(doc: &Option<String>, arities: Vec<(Params, Vec<Rc<Node>>)>) -> Node {
+	let mut items: Vector<Rc<Node>> = Vector::new();
+	items.push_back(Rc::new(Node::Symbol(symbol::FN)));
+	if let Some(ref doc) = *doc {
+		items.push_back(Rc::new(Node::Str(doc.clone())));
+	}
+	for (params, body) in arities {
+		let mut clause: Vector<Rc<Node>> = Vector::new();
+		clause.push_back(Rc::new(params.to_node()));
+		clause.extend(body);
+		items.push_back(Rc::new(Node::Expr(clause)));
+	}
+	Node::Expr(items)
+}
+
+#[cfg(test)]
+mod tests {
+	use interpreter::Interpreter;
+	use testing::{for_each_backend, ok};
+
+	#[test]
+	fn params() {
+		for_each_backend(|eval| {
+			assert_eq!(eval("(defn f {a b} { a b }) (f 1 2 3)"), Err("`f` takes 2 arguments, got 3".to_string()));
+			assert_eq!(eval("(f 1)"), Err("`f` takes 2 arguments, got 1".to_string()));
+			assert_eq!(eval("((fn {x} x))"), Err("fn takes 1 argument, got 0".to_string()));
+
+			// rest parameters collect the remaining arguments in a list
+			assert_eq!(eval("(defn g {a & more} { a more }) (list (g 1) (g 1 2 3))"), ok("{ { 1 { } } { 1 { 2 3 } } }"));
+			assert_eq!(eval("(g)"), Err("`g` takes at least 1 argument, got 0".to_string()));
+
+			// defaults are evaluated when the argument is missing, in the scope of the function
+			assert_eq!(eval("(def calls (atom 0)) (defn h {a (b (* a 2)) (c (swap! calls inc))} { a b c }) (list (h 1) (h 1 5 6) @calls)"), ok("{ { 1 2 1 } { 1 5 6 } 1 }"));
+			assert_eq!(eval("(h)"), Err("`h` takes 1 to 3 arguments, got 0".to_string()));
+			assert_eq!(eval("(defn k {(a 1) & more} { a more }) (k)"), ok("{ 1 { } }"));
+
+			// the first arity that accepts the arguments is called
+			assert_eq!(eval("(defn m \"Doc.\" ({} :none) ({x} :one) ({x y & more} :many)) (list (m) (m 1) (m 1 2) (m 1 2 3))"), ok("{ :none :one :many :many }"));
+			assert_eq!(eval("(defn n ({x} x) ({x y z} x) ({a b c d & more} a)) (n 1 2)"), Err("`n` takes 1, 3 or at least 4 arguments, got 2".to_string()));
+			assert_eq!(eval("(def atom2 (atom 1)) (swap! atom2 + 2 3)"), ok("6"));
+
+			assert_eq!(eval("(fn {(a 1) b} a)"), Err("illegal fn syntax: required parameter `b` after optional ones".to_string()));
+			assert_eq!(eval("(fn {a & b c} a)"), Err("illegal fn syntax: `&` takes exactly one rest parameter".to_string()));
+			assert_eq!(eval("(fn \"doc\")"), Err("illegal fn syntax".to_string()));
+		});
+
+		let mut interpreter = Interpreter::new();
+		interpreter.eval_str("(def g (fn \"Doc.\" ({} 0) ({x y} x)))").unwrap();
+		assert_eq!(interpreter.doc("g"), Some("(g) (g x y) Doc.".to_string()));
+		interpreter.eval_str("(defn f {a (b 1) & more} \"Doc.\" a)").unwrap();
+		assert_eq!(interpreter.doc("f"), Some("(f a (b ...) & more) Doc.".to_string()));
+	}
+
+	#[test]
+	fn destructuring() {
+		for_each_backend(|eval| {
+			// list and map patterns nest, also in optional and rest parameters
+			assert_eq!(eval("(defn f {{first second & rest} { name: n age: a }} { first second rest n a }) (f {1 2 3} { name: \"lou\" age: 3 })"), ok("{ 1 2 { 3 } lou 3 }"));
+			assert_eq!(eval("(defn g {({x y} {1 2}) & {z}} { x y z }) (list (g {3 4} 5) ((fn {{ p: { q: {r} } }} r) { p: { q: { 6 } } }))"), ok("{ { 3 4 5 } 6 }"));
+			assert_eq!(eval("(g)"), Err("{z} expects a list of 1 item, got { }".to_string()));
+			assert_eq!(eval("((fn {{a {b c}} & _} (list a b c)) { 1 { 2 3 } } 4)"), ok("{ 1 2 3 }"));
+
+			// let binds one after the other, the body sees all bindings
+			assert_eq!(eval("(let {x 1 {y & more} (list x 2 3) { k: v } { k: (+ y 1) }} { x y more v })"), ok("{ 1 1 { 2 3 } 2 }"));
+			assert_eq!(eval("(let {} 1)"), ok("1"));
+			assert_eq!(eval("(defn count-down {n} (let {m (- n 1)} (if (= m 0) :done (count-down m)))) (count-down 100)"), ok(":done"));
+			assert_eq!(eval("(let {x 1} (set! x (+ x 1)) x)"), ok("2"));
+			assert_eq!(eval("(let {x 1} (def from-let x)) from-let"), ok("1"));
+
+			// values of the wrong shape are reported with the pattern
+			assert_eq!(eval("(let {{a b} {1}} a)"), Err("{a b} expects a list of 2 items, got { 1 }".to_string()));
+			assert_eq!(eval("(let {{a & more} {}} a)"), Err("{a & more} expects a list of at least 1 item, got { }".to_string()));
+			assert_eq!(eval("(f 1 { name: 2 age: 3 })"), Err("{first second & rest} expects a list, got int".to_string()));
+			assert_eq!(eval("(f {1 2} { name: 2 })"), Err("{ age: a name: n } expects a map with the key `age`, got { name: 2 }".to_string()));
+			assert_eq!(eval("(let {{ a: x } 1} x)"), Err("{ a: x } expects a map, got int".to_string()));
+
+			assert_eq!(eval("(let {x} x)"), Err("illegal let syntax".to_string()));
+			assert_eq!(eval("(let {1 2} 3)"), Err("illegal let syntax".to_string()));
+			assert_eq!(eval("(let {{a & b c} {1}} a)"), Err("illegal pattern: `&` takes exactly one rest pattern".to_string()));
+		});
+
+		let mut interpreter = Interpreter::new();
+		interpreter.eval_str("(defn f {{a & more} ({ b: b } {:})} a)").unwrap();
+		assert_eq!(interpreter.doc("f"), Some("(f {a & more} ({ b: b } ...))".to_string()));
+	}
+
+	#[test]
+	fn matching() {
+		for_each_backend(|eval| {
+			// literals match equal values, _ anything and names bind the value
+			assert_eq!(eval("(defn describe {x} (match x 0 :zero \"a\" :a nil :nil :k :keyword true :true {} :empty _ :other)) (list (describe 0) (describe \"a\") (describe nil) (describe :k) (describe true) (describe {}) (describe 0.5))"), ok("{ :zero :a :nil :keyword :true :empty :other }"));
+			assert_eq!(eval("(match { 1 2 } {1 x} x _ 0)"), ok("2"));
+
+			// list and map patterns check the shape, the first matching clause wins
+			assert_eq!(eval("(defn area {shape} (match shape { type: :circle r: r } (* 3 r r) { type: :rect w: w h: h } (* w h) {:square s} (* s s))) (list (area { type: :circle r: 2 }) (area { type: :rect w: 2 h: 5 }) (area {:square 4}))"), ok("{ 12 10 16 }"));
+			assert_eq!(eval("(defn len {xs (n 0)} (match xs {} n {_ & more} (len more (inc n)))) (len { 1 2 3 })"), ok("3"));
+
+			// guards see the variables of the pattern
+			assert_eq!(eval("(defn size {n} (match n 0 :zero x :when (contains? #{ 1 2 3 } x) :small _ :big)) (list (size 0) (size 2) (size 9))"), ok("{ :zero :small :big }"));
+			assert_eq!(eval("(match { 1 1 } {a b} :when (= a b) :pair {a b} :two)"), ok(":pair"));
+
+			assert_eq!(eval("(area { type: :triangle })"), Err("no clause of match matches { type: :triangle }".to_string()));
+			assert_eq!(eval("(match 1 x)"), Err("illegal match syntax: no body for pattern x".to_string()));
+			assert_eq!(eval("(match 1 {a & b c} 2)"), Err("illegal pattern: `&` takes exactly one rest pattern".to_string()));
+
+			// literals and _ also work in parameters and let
+			assert_eq!(eval("(let {{:ok v _} { :ok 1 2 }} v)"), ok("1"));
+			assert_eq!(eval("(let {{:ok v} { :error 1 }} v)"), Err("expected :ok, got :error".to_string()));
+		});
+	}
+}
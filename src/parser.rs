@@ -1,49 +1,94 @@
-use std::collections::{LinkedList, HashMap};
+// Kept in the style of the original parser.
+#![allow(clippy::needless_return, clippy::ptr_arg, clippy::redundant_field_names)]
+
+use std::collections::BTreeMap;
 use std::iter::Peekable;
 use std::vec::IntoIter;
 use std::rc::Rc;
 
-use tokenizer::Token;
+use im_rc::Vector;
+
+use tokenizer::{self, Token, Tokenizer};
+use symbol::Symbol;
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum Node {
 	Str(String),
 	Int(i64),
 	Real(f64),
-	Symbol(String),
+	Symbol(Symbol),
+	Keyword(String),
 	Bool(bool),
 	Nil,
-	List(LinkedList<Rc<Node>>),
-	Map(HashMap<String, Rc<Node>>),
-	Expr(LinkedList<Rc<Node>>),
+	List(Vector<Rc<Node>>),
+	// sorted by key, so the values are evaluated in the same order every time
+	Map(BTreeMap<String, Rc<Node>>),
+	Set(Vector<Rc<Node>>),
+	Expr(Vector<Rc<Node>>),
+	// variables resolved by the analyzer: a local (depth, index) or a global slot
+	Local(usize, usize),
+	Global(usize)
 }
 pub type ParserResult = Result<Node, String>;
 
 pub struct Parser {
-	tokens: Peekable<IntoIter<Token>>
+	tokens: Peekable<IntoIter<Token>>,
+	positions: Vec<(usize, usize)>,
+	end: (usize, usize),
+	consumed: usize,
+	exhausted: bool
 }
 
 impl Parser {
+	#[cfg(test)]
 	pub fn new(tokens: Vec<Token>) -> Parser {
+		Parser::with_positions(tokens, vec![], (0, 0))
+	}
+
+	// `positions` are the line and column of every token, `end` the one of
+	// the end of the source
+	pub fn with_positions(tokens: Vec<Token>, positions: Vec<(usize, usize)>, end: (usize, usize)) -> Parser {
 		Parser {
-			tokens: tokens.into_iter().peekable()
+			tokens: tokens.into_iter().peekable(),
+			positions: positions,
+			end: end,
+			consumed: 0,
+			exhausted: false
 		}
 	}
 
+	fn advance(&mut self) -> Option<Token> {
+		let token = self.tokens.next();
+		match token {
+			Some(_) => { self.consumed += 1; },
+			None => { self.exhausted = true; }
+		}
+		token
+	}
+
+	// line and column of the last consumed token, the end of the source if
+	// the parser tried to read past it
+	pub fn position(&self) -> (usize, usize) {
+		if self.exhausted || self.consumed == 0 {
+			return self.end;
+		}
+		self.positions.get(self.consumed - 1).cloned().unwrap_or(self.end)
+	}
+
 	fn parse_collection(&mut self) -> ParserResult {
 		let mut is_map = false;
 		let mut is_list = false;
-		let mut list: LinkedList<Rc<Node>> = LinkedList::new();
-		let mut map: HashMap<String, Rc<Node>> = HashMap::new();
+		let mut list: Vector<Rc<Node>> = Vector::new();
+		let mut map: BTreeMap<String, Rc<Node>> = BTreeMap::new();
 		if self.tokens.peek() == Some(&Token::Colon) {
-			self.tokens.next().unwrap();
-			if self.tokens.next() == Some(Token::RightCurlyBracket) {
+			self.advance().unwrap();
+			if self.advance() == Some(Token::RightCurlyBracket) {
 				return Ok(Node::Map(map));
 			} else {
 				return Err("illegal collection litteral".to_string());
 			}
 		} else if self.tokens.peek() == Some(&Token::RightCurlyBracket) {
-			self.tokens.next().unwrap();
+			self.advance().unwrap();
 			return Ok(Node::List(list));
 		}
 
@@ -56,16 +101,16 @@ impl Parser {
 					}
 				} else { return Err("unexpected end of file".to_string()); }
 			} else if is_map {
-				let key: String = match self.tokens.next() {
+				let key: String = match self.advance() {
 					Some(token) => {
 						if let Token::Symbol(symbol) = token {
-							symbol
+							symbol.to_string()
 						} else { return Err("illegal map litteral".to_string()); }
 					},
 					None => { return Err("unexpected end of file".to_string()); }
 				};
 
-				if self.tokens.next() != Some(Token::Colon) {
+				if self.advance() != Some(Token::Colon) {
 					return Err("illegal map litteral".to_string());
 				}
 
@@ -92,10 +137,10 @@ impl Parser {
 				};
 
 				if self.tokens.peek() == Some(&Token::Colon) {
-					self.tokens.next().unwrap();
+					self.advance().unwrap();
 					is_map = true;
 					let key: String = match item {
-						Node::Symbol(symbol) => symbol,
+						Node::Symbol(symbol) => symbol.to_string(),
 						_ => { return Err("illegal collection litteral".to_string()); }
 					};
 
@@ -117,7 +162,7 @@ impl Parser {
 			}
 		}
 
-		if self.tokens.next().is_none() {
+		if self.advance().is_none() {
 			Err("unexpected end of file".to_string())
 		} else if is_map {
 			Ok(Node::Map(map))
@@ -128,6 +173,20 @@ impl Parser {
 		}
 	}
 
+	// the items of a set literal after `#{`
+	fn parse_set(&mut self) -> ParserResult {
+		let mut items: Vector<Rc<Node>> = Vector::new();
+		while self.tokens.peek() != Some(&Token::RightCurlyBracket) {
+			match self.next() {
+				Some(Ok(node)) => items.push_back(Rc::new(node)),
+				Some(Err(e)) => { return Err(e); },
+				None => { return Err("unexpected end of file".to_string()); }
+			}
+		}
+		self.advance().unwrap();
+		Ok(Node::Set(items))
+	}
+
 	/*pub fn collect(&mut self) -> Result<Vec<Node>, String> {
 		let mut nodes: Vec<Node> = vec![];
 		while let Some(res) = self.next() {
@@ -140,19 +199,72 @@ impl Parser {
 	}*/
 }
 
+// Parses all expressions in source, errors start with `line:column: `.
+pub fn parse(source: &String) -> Result<Vec<Node>, String> {
+	let mut tokenizer = Tokenizer::new(source);
+	let mut tokens: Vec<Token> = vec![];
+	let mut positions: Vec<(usize, usize)> = vec![];
+	while let Some(res) = tokenizer.next() {
+		match res {
+			Ok(token) => {
+				tokens.push(token);
+				positions.push(tokenizer.position());
+			},
+			Err(e) => {
+				let (line, column) = tokenizer.position();
+				return Err(format!("{}:{}: {}", line, column, e));
+			}
+		}
+	}
+
+	let mut parser = Parser::with_positions(tokens, positions, tokenizer.position());
+	let mut nodes: Vec<Node> = vec![];
+	while let Some(res) = parser.next() {
+		match res {
+			Ok(node) => nodes.push(node),
+			Err(e) => {
+				let (line, column) = parser.position();
+				return Err(format!("{}:{}: {}", line, column, e));
+			}
+		}
+	}
+	Ok(nodes)
+}
+
+// Whether `source` ends inside of a string or with unclosed brackets, so
+// that more input could complete it (used by the REPL).
+pub fn is_incomplete(source: &String) -> bool {
+	let mut depth: i32 = 0;
+	for res in Tokenizer::new(source) {
+		match res {
+			Ok(Token::LeftBracket) | Ok(Token::LeftCurlyBracket) | Ok(Token::HashCurlyBracket) => { depth += 1; },
+			Ok(Token::RightBracket) | Ok(Token::RightCurlyBracket) => {
+				depth -= 1;
+				if depth < 0 {
+					return false;
+				}
+			},
+			Ok(_) => {},
+			Err(e) => { return e == tokenizer::UNTERMINATED_STRING; }
+		}
+	}
+	depth > 0
+}
+
 impl Iterator for Parser {
 	type Item = ParserResult;
 	fn next(&mut self) -> Option<ParserResult> {
-		if let Some(token) = self.tokens.next() {
+		if let Some(token) = self.advance() {
 			match token {
 				Token::Str(string) => Some(Ok(Node::Str(string))),
 				Token::Int(number) => Some(Ok(Node::Int(number))),
 				Token::Real(number) => Some(Ok(Node::Real(number))),
 				Token::Bool(value) => Some(Ok(Node::Bool(value))),
-				Token::Symbol(string) => Some(Ok(Node::Symbol(string))),
+				Token::Symbol(symbol) => Some(Ok(Node::Symbol(symbol))),
+				Token::Keyword(string) => Some(Ok(Node::Keyword(string))),
 				Token::Nil => Some(Ok(Node::Nil)),
 				Token::LeftBracket => {
-					let mut items: LinkedList<Rc<Node>> = LinkedList::new();
+					let mut items: Vector<Rc<Node>> = Vector::new();
 					while self.tokens.peek() != Some(&Token::RightBracket) {
 						if let Some(res) = self.next() {
 							match res {
@@ -162,10 +274,16 @@ impl Iterator for Parser {
 						} else { return Some(Err("unexpected end of file".to_string())); }
 					}
 
-					self.tokens.next().expect("parsing expression fail: unexpected end of file");
+					self.advance().expect("parsing expression fail: unexpected end of file");
 					Some(Ok(Node::Expr(items)))
 				},
 				Token::LeftCurlyBracket => Some(self.parse_collection()),
+				Token::HashCurlyBracket => Some(self.parse_set()),
+				Token::At => match self.next() {
+					Some(Ok(node)) => Some(Ok(Node::Expr(vec![Rc::new(Node::Symbol(Symbol::intern("deref"))), Rc::new(node)].into_iter().collect()))),
+					Some(Err(e)) => Some(Err(e)),
+					None => Some(Err("unexpected end of file".to_string()))
+				},
 				_ => Some(Err("unexpected token".to_string()))
 			}
 		} else {
@@ -189,12 +307,12 @@ mod tests {
 	#[test]
 	fn collections() {
 		let mut ast = parse(" {} {:} { 1 2 3 } { hallo: \"welt\" abc: 987 } ").unwrap().into_iter();
-		let mut list = LinkedList::new();
-		let mut map = HashMap::new();
+		let mut list = Vector::new();
+		let mut map = BTreeMap::new();
 
 
-		assert_eq!(ast.next().unwrap(), Node::List(LinkedList::new()));
-		assert_eq!(ast.next().unwrap(), Node::Map(HashMap::new()));
+		assert_eq!(ast.next().unwrap(), Node::List(Vector::new()));
+		assert_eq!(ast.next().unwrap(), Node::Map(BTreeMap::new()));
 		
 		list.push_back(Rc::new( Node::Int(1) ));
 		list.push_back(Rc::new( Node::Int(2) ));
@@ -207,5 +325,44 @@ mod tests {
 		assert_eq!(ast.next(), None);
 	}
 
+	#[test]
+	fn sets() {
+		let mut ast = parse(" #{} #{ 1 { 2 } } ").unwrap().into_iter();
+		assert_eq!(ast.next().unwrap(), Node::Set(Vector::new()));
+
+		let mut items = Vector::new();
+		items.push_back(Rc::new( Node::Int(1) ));
+		items.push_back(Rc::new( Node::List(vec![Rc::new( Node::Int(2) )].into_iter().collect()) ));
+		assert_eq!(ast.next().unwrap(), Node::Set(items));
+		assert_eq!(ast.next(), None);
+		assert_eq!(parse("#{ 1 "), Err("unexpected end of file".to_string()));
+	}
+
+	#[test]
+	fn deref() {
+		assert_eq!(parse("@a"), parse("(deref a)"));
+		assert_eq!(parse("@(f 1)"), parse("(deref (f 1))"));
+		assert_eq!(parse("@"), Err("unexpected end of file".to_string()));
+	}
+
+	#[test]
+	fn incomplete() {
+		assert!(is_incomplete(&"(def f (fn {x}".to_string()));
+		assert!(is_incomplete(&"(echo \"abc\n".to_string()));
+		assert!(is_incomplete(&"{ a: 1".to_string()));
+		assert!(is_incomplete(&"#{ 1 2".to_string()));
+		assert!(!is_incomplete(&"(echo 1) (echo 2)".to_string()));
+		assert!(!is_incomplete(&"(echo 1))) (".to_string()));
+		assert!(!is_incomplete(&"".to_string()));
+	}
+
+	#[test]
+	fn positions() {
+		assert_eq!(super::parse(&"(echo 1)\n  (echo )) ".to_string()), Err("2:10: unexpected token".to_string()));
+		assert_eq!(super::parse(&"{ a: 1\n b 2 }".to_string()), Err("2:4: illegal map litteral".to_string()));
+		assert_eq!(super::parse(&"(def x\n\t{ 1 2 ".to_string()), Err("2:8: unexpected end of file".to_string()));
+		assert_eq!(super::parse(&"; comment\n0x".to_string()), Err("2:1: illegal number litteral".to_string()));
+	}
+
 }
 
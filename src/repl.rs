@@ -0,0 +1,275 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Instant;
+
+use rustyline::{Context, Editor, Helper};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::{CmdKind, Highlighter, MatchingBracketHighlighter};
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+
+use lial::{Interpreter, Value, Error};
+
+struct LialHelper {
+	names: Vec<String>,
+	brackets: MatchingBracketHighlighter
+}
+
+impl Completer for LialHelper {
+	type Candidate = String;
+
+	fn complete(&self, line: &str, pos: usize, _ctx: &Context) -> rustyline::Result<(usize, Vec<String>)> {
+		let start = line[..pos].char_indices().rev()
+			.take_while(|&(_, c)| lial::is_symbolchar(c))
+			.last().map(|(i, _)| i).unwrap_or(pos);
+		let prefix = &line[start..pos];
+		if prefix.is_empty() {
+			return Ok((pos, vec![]));
+		}
+
+		let candidates = self.names.iter()
+			.filter(|name| name.starts_with(prefix))
+			.cloned().collect();
+		Ok((start, candidates))
+	}
+}
+
+impl Highlighter for LialHelper {
+	fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+		self.brackets.highlight(line, pos)
+	}
+
+	fn highlight_char(&self, line: &str, pos: usize, kind: CmdKind) -> bool {
+		self.brackets.highlight_char(line, pos, kind)
+	}
+}
+
+impl Hinter for LialHelper {
+	type Hint = String;
+}
+
+impl Validator for LialHelper {}
+
+impl Helper for LialHelper {}
+
+const HELP: &str = "\
+:doc <name>    show the documentation of name
+:env           list the definitions made in this session
+:load <file>   evaluate a file
+:reset         start over with a fresh interpreter
+:time <expr>   evaluate expr and print how long it took
+:type <expr>   evaluate expr and print the type of the result
+:help          show this help
+
+*1, *2 and *3 are bound to the last three results, *e to the last error.";
+
+fn history_file() -> Option<PathBuf> {
+	env::var_os("HOME").map(|home| PathBuf::from(home).join(".lial_history"))
+}
+
+struct Session<F: Fn() -> Interpreter> {
+	new_interpreter: F,
+	interpreter: Interpreter,
+	// definitions made before the session started, hidden from `:env`
+	base: HashMap<String, Rc<Value>>,
+	results: Vec<Rc<Value>>
+}
+
+impl<F: Fn() -> Interpreter> Session<F> {
+	fn new(new_interpreter: F) -> Session<F> {
+		let interpreter = new_interpreter();
+		Session {
+			base: interpreter.globals().into_iter().collect(),
+			new_interpreter: new_interpreter,
+			interpreter: interpreter,
+			results: vec![]
+		}
+	}
+
+	fn record(&mut self, res: Result<Vec<Rc<Value>>, Error>) -> Option<Vec<Rc<Value>>> {
+		match res {
+			Ok(values) => {
+				for value in &values {
+					self.results.insert(0, value.clone());
+				}
+				self.results.truncate(3);
+				for (i, value) in self.results.iter().enumerate() {
+					self.interpreter.define(&format!("*{}", i + 1), value.clone());
+				}
+				Some(values)
+			},
+			Err(e) => {
+				perror!("Error: {:?}", e.message());
+				self.interpreter.define("*e", Rc::new(Value::Str(e.message().to_string())));
+				None
+			}
+		}
+	}
+
+	fn eval(&mut self, source: &str) {
+		let res = self.interpreter.eval_all(source);
+		if let Some(values) = self.record(res) {
+			let mut i = 0;
+			for value in values {
+				perror!("${} = {:?}", i, value);
+				i += 1;
+			}
+		}
+	}
+
+	fn command(&mut self, command: &str, arg: &str) {
+		match command {
+			"doc" => match self.interpreter.doc(arg) {
+				Some(doc) => perror!("{}", doc),
+				None => perror!("`{}` is not defined", arg)
+			},
+			"env" => {
+				for (name, value) in self.interpreter.globals() {
+					let predefined = match self.base.get(&name) {
+						Some(base) => Rc::ptr_eq(base, &value),
+						None => ["*1", "*2", "*3", "*e"].contains(&name.as_str())
+					};
+					if !predefined {
+						perror!("{}: {}", name, value.type_name());
+					}
+				}
+			},
+			"load" => {
+				let res = self.interpreter.eval_file(Path::new(arg)).map(|value| vec![value]);
+				if let Some(values) = self.record(res) {
+					perror!("{:?}", values[0]);
+				}
+			},
+			"reset" => {
+				self.interpreter = (self.new_interpreter)();
+				self.base = self.interpreter.globals().into_iter().collect();
+				self.results.clear();
+			},
+			"time" => {
+				let start = Instant::now();
+				self.eval(arg);
+				perror!("Elapsed: {:?}", start.elapsed());
+			},
+			"type" => {
+				let res = self.interpreter.eval_all(arg);
+				if let Some(values) = self.record(res) {
+					for value in values {
+						perror!("{}", value.type_name());
+					}
+				}
+			},
+			"help" => perror!("{}", HELP),
+			_ => unreachable!("unknown command `:{}`", command)
+		}
+	}
+}
+
+const COMMANDS: &[&str] = &["doc", "env", "load", "reset", "time", "type", "help"];
+
+// `:name arg` is a command if name is one of COMMANDS, everything else
+// (like the keyword `:name`) is evaluated
+fn parse_command(input: &str) -> Option<(&str, &str)> {
+	let input = input.trim();
+	if !input.starts_with(':') {
+		return None;
+	}
+
+	let (command, arg) = match input.find(char::is_whitespace) {
+		Some(pos) => (&input[1..pos], input[pos..].trim()),
+		None => (&input[1..], "")
+	};
+	if COMMANDS.contains(&command) {
+		Some((command, arg))
+	} else {
+		None
+	}
+}
+
+pub fn run<F: Fn() -> Interpreter>(new_interpreter: F) {
+	println!("lial: REPL (Ctrl+D to exit, :help for commands)");
+	let mut editor: Editor<LialHelper, DefaultHistory> = match Editor::new() {
+		Ok(editor) => editor,
+		Err(e) => {
+			perror!("Error: {:?}", e);
+			::std::process::exit(1);
+		}
+	};
+	editor.set_helper(Some(LialHelper {
+		names: vec![],
+		brackets: MatchingBracketHighlighter::new()
+	}));
+	let history = history_file();
+	if let Some(ref path) = history {
+		editor.load_history(path).ok();
+	}
+
+	let mut session = Session::new(new_interpreter);
+	let mut buf = String::new();
+	loop {
+		if let Some(helper) = editor.helper_mut() {
+			helper.names = session.interpreter.names();
+		}
+
+		// keep reading lines while the form is not complete yet
+		match editor.readline(if buf.is_empty() { ">_ " } else { ".. " }) {
+			Ok(line) => {
+				buf.push_str(&line);
+				buf.push('\n');
+				if lial::is_incomplete(&buf) { continue; }
+
+				editor.add_history_entry(buf.trim_end()).ok();
+				match parse_command(&buf) {
+					Some((command, arg)) => session.command(command, arg),
+					None => session.eval(&buf)
+				}
+				buf.clear();
+			},
+			Err(ReadlineError::Interrupted) => {
+				// Ctrl+C only discards the current input
+				buf.clear();
+			},
+			Err(ReadlineError::Eof) => {
+				if !buf.is_empty() {
+					session.eval(&buf);
+				}
+				break;
+			},
+			Err(e) => {
+				perror!("Error: {:?}", e);
+				::std::process::exit(1);
+			}
+		}
+	}
+
+	if let Some(ref path) = history {
+		if let Err(e) = editor.save_history(path) {
+			perror!("Error: {}: {}", path.display(), e);
+		}
+	}
+	println!("\nBye!");
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn commands() {
+		assert_eq!(parse_command(":doc inc\n"), Some(("doc", "inc")));
+		assert_eq!(parse_command("  :time  (fac 10)\n"), Some(("time", "(fac 10)")));
+		assert_eq!(parse_command(":env"), Some(("env", "")));
+		assert_eq!(parse_command("(echo :doc)"), None);
+		assert_eq!(parse_command(":1"), None);
+
+		// keywords that are not commands are evaluated
+		assert_eq!(parse_command(":kw"), None);
+		assert_eq!(parse_command(":foo bar"), None);
+		assert_eq!(parse_command(":docs"), None);
+	}
+}
@@ -0,0 +1,287 @@
+use std::error;
+use std::fmt;
+use std::rc::Rc;
+
+use im_rc::Vector;
+
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq};
+
+use interpreter::Value;
+use collections::{self, Map};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+
+impl error::Error for Error {}
+
+impl ser::Error for Error {
+	fn custom<T: fmt::Display>(msg: T) -> Error {
+		Error(msg.to_string())
+	}
+}
+
+impl ::serde::de::Error for Error {
+	fn custom<T: fmt::Display>(msg: T) -> Error {
+		Error(msg.to_string())
+	}
+}
+
+// Keywords are serialized as their name, functions and macros cannot be
+// serialized at all.
+impl Serialize for Value {
+	fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			Value::Str(s) => serializer.serialize_str(s),
+			&Value::Int(i) => serializer.serialize_i64(i),
+			&Value::Real(r) => serializer.serialize_f64(r),
+			&Value::Bool(b) => serializer.serialize_bool(b),
+			&Value::Nil => serializer.serialize_unit(),
+			Value::Keyword(name) => serializer.serialize_str(name),
+			Value::List(list) => {
+				let mut seq = serializer.serialize_seq(Some(list.len()))?;
+				for item in list {
+					seq.serialize_element(&**item)?;
+				}
+				seq.end()
+			},
+			Value::Set(set) => {
+				let mut seq = serializer.serialize_seq(Some(set.len()))?;
+				for item in collections::sorted_set(set) {
+					seq.serialize_element(&**item)?;
+				}
+				seq.end()
+			},
+			Value::Map(map) => {
+				let mut entries = serializer.serialize_map(Some(map.len()))?;
+				for (key, value) in collections::sorted(map) {
+					entries.serialize_entry(key, &**value)?;
+				}
+				entries.end()
+			},
+			_ => Err(ser::Error::custom(format!("cannot serialize a value of type {}", self.type_name())))
+		}
+	}
+}
+
+/// Converts any serializable Rust value into a `Value`: structs and maps
+/// become maps, sequences and tuples lists, `None` and `()` nil and unit
+/// enum variants keywords.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, Error> {
+	value.serialize(Serializer)
+}
+
+pub struct Serializer;
+
+fn single_entry(key: &str, value: Value) -> Value {
+	let mut map: Map<String, Rc<Value>> = Map::default();
+	map.insert(key.to_string(), Rc::new(value));
+	Value::Map(map)
+}
+
+impl ser::Serializer for Serializer {
+	type Ok = Value;
+	type Error = Error;
+	type SerializeSeq = SeqSerializer;
+	type SerializeTuple = SeqSerializer;
+	type SerializeTupleStruct = SeqSerializer;
+	type SerializeTupleVariant = SeqSerializer;
+	type SerializeMap = MapSerializer;
+	type SerializeStruct = MapSerializer;
+	type SerializeStructVariant = MapSerializer;
+
+	fn serialize_bool(self, v: bool) -> Result<Value, Error> { Ok(Value::Bool(v)) }
+	fn serialize_i8(self, v: i8) -> Result<Value, Error> { Ok(Value::Int(v as i64)) }
+	fn serialize_i16(self, v: i16) -> Result<Value, Error> { Ok(Value::Int(v as i64)) }
+	fn serialize_i32(self, v: i32) -> Result<Value, Error> { Ok(Value::Int(v as i64)) }
+	fn serialize_i64(self, v: i64) -> Result<Value, Error> { Ok(Value::Int(v)) }
+	fn serialize_u8(self, v: u8) -> Result<Value, Error> { Ok(Value::Int(v as i64)) }
+	fn serialize_u16(self, v: u16) -> Result<Value, Error> { Ok(Value::Int(v as i64)) }
+	fn serialize_u32(self, v: u32) -> Result<Value, Error> { Ok(Value::Int(v as i64)) }
+
+	fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+		if v > i64::MAX as u64 {
+			return Err(Error(format!("{} does not fit into an int", v)));
+		}
+		Ok(Value::Int(v as i64))
+	}
+
+	fn serialize_f32(self, v: f32) -> Result<Value, Error> { Ok(Value::Real(v as f64)) }
+	fn serialize_f64(self, v: f64) -> Result<Value, Error> { Ok(Value::Real(v)) }
+	fn serialize_char(self, v: char) -> Result<Value, Error> { Ok(Value::Str(v.to_string())) }
+	fn serialize_str(self, v: &str) -> Result<Value, Error> { Ok(Value::Str(v.to_string())) }
+
+	fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+		Ok(Value::List(v.iter().map(|&b| Rc::new(Value::Int(b as i64))).collect()))
+	}
+
+	fn serialize_none(self) -> Result<Value, Error> { Ok(Value::Nil) }
+
+	fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, Error> {
+		value.serialize(self)
+	}
+
+	fn serialize_unit(self) -> Result<Value, Error> { Ok(Value::Nil) }
+	fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> { Ok(Value::Nil) }
+
+	fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Value, Error> {
+		Ok(Value::Keyword(variant.to_string()))
+	}
+
+	fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<Value, Error> {
+		value.serialize(self)
+	}
+
+	fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32, variant: &'static str, value: &T) -> Result<Value, Error> {
+		Ok(single_entry(variant, value.serialize(Serializer)?))
+	}
+
+	fn serialize_seq(self, _len: Option<usize>) -> Result<SeqSerializer, Error> {
+		Ok(SeqSerializer { variant: None, items: Vector::new() })
+	}
+
+	fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, Error> {
+		self.serialize_seq(Some(len))
+	}
+
+	fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer, Error> {
+		self.serialize_seq(Some(len))
+	}
+
+	fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str, _len: usize) -> Result<SeqSerializer, Error> {
+		Ok(SeqSerializer { variant: Some(variant), items: Vector::new() })
+	}
+
+	fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer, Error> {
+		Ok(MapSerializer { variant: None, map: Map::default(), key: None })
+	}
+
+	fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer, Error> {
+		self.serialize_map(Some(len))
+	}
+
+	fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str, _len: usize) -> Result<MapSerializer, Error> {
+		Ok(MapSerializer { variant: Some(variant), map: Map::default(), key: None })
+	}
+}
+
+pub struct SeqSerializer {
+	variant: Option<&'static str>,
+	items: Vector<Rc<Value>>
+}
+
+impl SeqSerializer {
+	fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+		self.items.push_back(Rc::new(value.serialize(Serializer)?));
+		Ok(())
+	}
+
+	fn finish(self) -> Result<Value, Error> {
+		let list = Value::List(self.items);
+		match self.variant {
+			Some(variant) => Ok(single_entry(variant, list)),
+			None => Ok(list)
+		}
+	}
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+	type Ok = Value;
+	type Error = Error;
+	fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> { self.push(value) }
+	fn end(self) -> Result<Value, Error> { self.finish() }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+	type Ok = Value;
+	type Error = Error;
+	fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> { self.push(value) }
+	fn end(self) -> Result<Value, Error> { self.finish() }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+	type Ok = Value;
+	type Error = Error;
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> { self.push(value) }
+	fn end(self) -> Result<Value, Error> { self.finish() }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+	type Ok = Value;
+	type Error = Error;
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> { self.push(value) }
+	fn end(self) -> Result<Value, Error> { self.finish() }
+}
+
+pub struct MapSerializer {
+	variant: Option<&'static str>,
+	map: Map<String, Rc<Value>>,
+	key: Option<String>
+}
+
+impl MapSerializer {
+	fn finish(self) -> Result<Value, Error> {
+		let map = Value::Map(self.map);
+		match self.variant {
+			Some(variant) => Ok(single_entry(variant, map)),
+			None => Ok(map)
+		}
+	}
+}
+
+// Map keys have to be strings (or keywords), numbers and bools are
+// converted to strings.
+fn map_key(key: Value) -> Result<String, Error> {
+	match key {
+		Value::Str(s) | Value::Keyword(s) => Ok(s),
+		Value::Int(_) | Value::Real(_) | Value::Bool(_) => Ok(key.to_string()),
+		_ => Err(Error(format!("map keys must be strings, found {}", key.type_name())))
+	}
+}
+
+impl ser::SerializeMap for MapSerializer {
+	type Ok = Value;
+	type Error = Error;
+
+	fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+		self.key = Some(map_key(key.serialize(Serializer)?)?);
+		Ok(())
+	}
+
+	fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+		let key = self.key.take().expect("serialize_value called before serialize_key");
+		self.map.insert(key, Rc::new(value.serialize(Serializer)?));
+		Ok(())
+	}
+
+	fn end(self) -> Result<Value, Error> { self.finish() }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+	type Ok = Value;
+	type Error = Error;
+
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+		self.map.insert(key.to_string(), Rc::new(value.serialize(Serializer)?));
+		Ok(())
+	}
+
+	fn end(self) -> Result<Value, Error> { self.finish() }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+	type Ok = Value;
+	type Error = Error;
+
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+		self.map.insert(key.to_string(), Rc::new(value.serialize(Serializer)?));
+		Ok(())
+	}
+
+	fn end(self) -> Result<Value, Error> { self.finish() }
+}
@@ -0,0 +1,121 @@
+use std::rc::Rc;
+
+use interpreter::{Value, EvalResult, Builtins};
+use collections::Set;
+
+// Sets are persistent (see `collections`) and written `#{ 1 2 3 }`, the
+// operations below return new sets.
+pub const BUILTINS: &Builtins = &[
+	("set", set, "(set list) Returns a set of the items of list."),
+	("contains?", contains, "(contains? set x) Whether x is in set, (contains? map key) whether map has key."),
+	("union", union, "(union set ...) Returns a set of the items that are in any of the sets."),
+	("intersection", intersection, "(intersection set ...) Returns a set of the items that are in all sets."),
+	("difference", difference, "(difference set ...) Returns a set of the items of the first set that are in none of the others."),
+	("subset?", subset, "(subset? a b) Whether every item of the set a is in b.")
+];
+
+fn set_arg<'a>(name: &str, value: &'a Value) -> Result<&'a Set<Rc<Value>>, String> {
+	match value {
+		Value::Set(set) => Ok(set),
+		_ => Err(format!("{} takes sets, got {}", name, value.type_name()))
+	}
+}
+
+// all arguments, at least one
+fn sets_arg<'a>(name: &str, args: &'a [Rc<Value>]) -> Result<Vec<&'a Set<Rc<Value>>>, String> {
+	if args.is_empty() {
+		return Err(format!("{} takes at least one set", name));
+	}
+	args.iter().map(|arg| set_arg(name, arg)).collect()
+}
+
+pub fn set(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 1 {
+		return Err("set takes only one argument of type list".into());
+	}
+
+	match *args[0] {
+		Value::List(ref list) => Ok(Rc::new(Value::Set(list.iter().cloned().collect()))),
+		Value::Set(_) => Ok(args[0].clone()),
+		ref value => Err(format!("set takes a list, got {}", value.type_name()).into())
+	}
+}
+
+pub fn contains(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 2 {
+		return Err("contains? takes a set (or map) and a value".into());
+	}
+
+	let found = match (&*args[0], &*args[1]) {
+		(Value::Set(set), _) => set.contains(&args[1]),
+		(&Value::Map(ref map), &Value::Str(ref key)) | (&Value::Map(ref map), &Value::Keyword(ref key)) => map.contains_key(key),
+		(&Value::Map(_), _) => false,
+		(value, _) => { return Err(format!("contains? takes a set or map, got {}", value.type_name()).into()); }
+	};
+	Ok(Rc::new(Value::Bool(found)))
+}
+
+pub fn union(args: Vec<Rc<Value>>) -> EvalResult {
+	let sets = sets_arg("union", &args)?;
+	let mut res = sets[0].clone();
+	for other in &sets[1..] {
+		res = res.union((*other).clone());
+	}
+	Ok(Rc::new(Value::Set(res)))
+}
+
+pub fn intersection(args: Vec<Rc<Value>>) -> EvalResult {
+	let sets = sets_arg("intersection", &args)?;
+	let mut res = sets[0].clone();
+	for other in &sets[1..] {
+		res.retain(|item| other.contains(item));
+	}
+	Ok(Rc::new(Value::Set(res)))
+}
+
+pub fn difference(args: Vec<Rc<Value>>) -> EvalResult {
+	let sets = sets_arg("difference", &args)?;
+	let mut res = sets[0].clone();
+	for other in &sets[1..] {
+		res.retain(|item| !other.contains(item));
+	}
+	Ok(Rc::new(Value::Set(res)))
+}
+
+pub fn subset(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 2 {
+		return Err("subset? takes two sets".into());
+	}
+
+	let a = set_arg("subset?", &args[0])?;
+	let b = set_arg("subset?", &args[1])?;
+	Ok(Rc::new(Value::Bool(a.is_subset(b))))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use testing::{eval, printed, boolean};
+
+	#[test]
+	fn sets() {
+		assert_eq!(printed("#{ 3 1 2 1 }"), "#{ 1 2 3 }");
+		assert_eq!(printed("#{ \"b\" :a 2.5 1 { 1 } (+ 1 2) nil }"), "#{ <Nil> 1 2.5 3 b :a { 1 } }");
+		assert_eq!(printed("#{}"), "#{ }");
+		assert_eq!(eval("(= #{ 1 2 } #{ 2 1 } (set { 1 2 2 }))"), boolean(true));
+		assert_eq!(eval("(= #{ 1 2 } { 1 2 })"), boolean(false));
+		assert_eq!(eval("(= #{ #{ 1 2 } { a: 1 } } #{ { a: 1 } #{ 2 1 } })"), boolean(true));
+
+		assert_eq!(eval("(contains? #{ 1 \"a\" { 2 } } { 2 })"), boolean(true));
+		assert_eq!(eval("(contains? #{ 1 } 1.0)"), boolean(false));
+		assert_eq!(eval("(contains? { a: 1 } :a)"), boolean(true));
+		assert_eq!(eval("(contains? { 1 } 1)"), Err("contains? takes a set or map, got list".into()));
+
+		assert_eq!(printed("(union #{ 1 2 } #{ 2 3 } #{ 4 })"), "#{ 1 2 3 4 }");
+		assert_eq!(printed("(intersection #{ 1 2 3 } #{ 2 3 4 } #{ 3 2 })"), "#{ 2 3 }");
+		assert_eq!(printed("(difference #{ 1 2 3 } #{ 2 } #{ 3 })"), "#{ 1 }");
+		assert_eq!(eval("{ (subset? #{ 1 } #{ 1 2 }) (subset? #{ 1 3 } #{ 1 2 }) (subset? #{} #{}) }"), eval("{ true false true }"));
+		assert_eq!(eval("(union #{ 1 } { 2 })"), Err("union takes sets, got list".into()));
+		assert_eq!(eval("(count (conj #{ 1 } 1 2))"), Ok(Rc::new(Value::Int(2))));
+	}
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+/// An interned name: the index of the name in a global table, so symbols
+/// are copied, compared and hashed like integers. The tokenizer interns every
+/// symbol it reads, embedders can intern names up front with
+/// `Symbol::intern`. Interned names are never freed.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+// interned first and in this order, so the constants below match them
+const PREDEFINED: &[&str] = &["fn", "def", "defmacro", "do", "if", "and", "or", "import", "&", "set!", "try", "catch", "finally", "let", "match", "apply"];
+
+pub const FN: Symbol = Symbol(0);
+pub const DEF: Symbol = Symbol(1);
+pub const DEFMACRO: Symbol = Symbol(2);
+pub const DO: Symbol = Symbol(3);
+pub const IF: Symbol = Symbol(4);
+pub const AND: Symbol = Symbol(5);
+pub const OR: Symbol = Symbol(6);
+pub const IMPORT: Symbol = Symbol(7);
+pub const AMPERSAND: Symbol = Symbol(8);
+pub const SET: Symbol = Symbol(9);
+pub const TRY: Symbol = Symbol(10);
+pub const CATCH: Symbol = Symbol(11);
+pub const FINALLY: Symbol = Symbol(12);
+pub const LET: Symbol = Symbol(13);
+pub const MATCH: Symbol = Symbol(14);
+pub const APPLY: Symbol = Symbol(15);
+
+struct Interner {
+	ids: HashMap<&'static str, Symbol>,
+	names: Vec<&'static str>
+}
+
+impl Interner {
+	fn intern(&mut self, name: &str) -> Symbol {
+		if let Some(&symbol) = self.ids.get(name) {
+			return symbol;
+		}
+		let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+		let symbol = Symbol(self.names.len() as u32);
+		self.names.push(name);
+		self.ids.insert(name, symbol);
+		symbol
+	}
+}
+
+fn interner() -> MutexGuard<'static, Interner> {
+	static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+	let interner = INTERNER.get_or_init(|| {
+		let mut interner = Interner { ids: HashMap::new(), names: vec![] };
+		for name in PREDEFINED {
+			interner.intern(name);
+		}
+		Mutex::new(interner)
+	});
+	// the table stays consistent even if another thread panicked
+	interner.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Symbol {
+	pub fn intern(name: &str) -> Symbol {
+		interner().intern(name)
+	}
+
+	/// The symbol of `name` if it was interned before.
+	pub fn find(name: &str) -> Option<Symbol> {
+		interner().ids.get(name).cloned()
+	}
+
+	pub fn as_str(self) -> &'static str {
+		interner().names[self.0 as usize]
+	}
+}
+
+/// Interns all names at once, e.g. the globals an embedder is going to define.
+pub fn intern_all(names: &[&str]) -> Vec<Symbol> {
+	let mut interner = interner();
+	names.iter().map(|name| interner.intern(name)).collect()
+}
+
+impl<'a> From<&'a str> for Symbol {
+	fn from(name: &'a str) -> Symbol {
+		Symbol::intern(name)
+	}
+}
+
+impl PartialEq<str> for Symbol {
+	fn eq(&self, other: &str) -> bool {
+		self.as_str() == other
+	}
+}
+
+impl fmt::Display for Symbol {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+impl fmt::Debug for Symbol {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Symbol({})", self.as_str())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn interning() {
+		assert_eq!(Symbol::intern("echo"), Symbol::from("echo"));
+		assert!(Symbol::intern("echo") != Symbol::intern("echo2"));
+		assert_eq!(Symbol::intern("echo").as_str(), "echo");
+		assert_eq!(Symbol::intern("if"), IF);
+		assert_eq!(Symbol::intern("&"), AMPERSAND);
+		assert_eq!(Symbol::intern("set!"), SET);
+		assert_eq!(Symbol::intern("finally"), FINALLY);
+		assert_eq!(Symbol::intern("let"), LET);
+		assert_eq!(Symbol::intern("match"), MATCH);
+		assert_eq!(Symbol::intern("apply"), APPLY);
+		assert_eq!(Symbol::find("never-interned-anywhere"), None);
+
+		let symbols = intern_all(&["x", "y"]);
+		assert_eq!(symbols, vec![Symbol::intern("x"), Symbol::intern("y")]);
+		assert_eq!(Symbol::find("y"), Some(symbols[1]));
+		assert_eq!(format!("{} {:?}", symbols[0], symbols[1]), "x Symbol(y)");
+	}
+}
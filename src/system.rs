@@ -0,0 +1,115 @@
+use std::env;
+use std::io::{self, Read, Write};
+use std::process;
+use std::rc::Rc;
+
+use interpreter::{Value, EvalResult, Builtins};
+
+pub const BUILTINS: &Builtins = &[
+	("echo", echo, "(echo x ...) Prints all arguments followed by a newline."),
+	("read-line", read_line, "(read-line) Reads one line from stdin, nil at the end of input."),
+	("read-all", read_all, "(read-all) Reads everything left on stdin."),
+	("getenv", getenv, "(getenv name) Value of the environment variable or nil."),
+	("setenv", setenv, "(setenv name x) Sets the environment variable."),
+	("exit", exit, "(exit) (exit code) Terminates the program with the status code (default 0).")
+];
+
+pub fn echo(args: Vec<Rc<Value>>) -> EvalResult {
+	for arg in args {
+		print!("{}", arg.to_string());
+	}
+	println!();
+	Ok(Rc::new(Value::Nil))
+}
+
+pub fn read_line(args: Vec<Rc<Value>>) -> EvalResult {
+	if !args.is_empty() {
+		return Err("read-line takes no arguments".into());
+	}
+
+	let mut buf = String::new();
+	match io::stdin().read_line(&mut buf) {
+		Ok(0) => Ok(Rc::new(Value::Nil)),
+		Ok(_) => {
+			if buf.ends_with('\n') {
+				buf.pop();
+				if buf.ends_with('\r') {
+					buf.pop();
+				}
+			}
+			Ok(Rc::new(Value::Str(buf)))
+		},
+		Err(e) => Err(format!("read-line: {}", e).into())
+	}
+}
+
+pub fn read_all(args: Vec<Rc<Value>>) -> EvalResult {
+	if !args.is_empty() {
+		return Err("read-all takes no arguments".into());
+	}
+
+	let mut buf = String::new();
+	match io::stdin().read_to_string(&mut buf) {
+		Ok(_) => Ok(Rc::new(Value::Str(buf))),
+		Err(e) => Err(format!("read-all: {}", e).into())
+	}
+}
+
+pub fn getenv(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 1 {
+		return Err("getenv takes only one argument of type string".into());
+	}
+
+	match *args[0] {
+		Value::Str(ref name) => match env::var(name) {
+			Ok(value) => Ok(Rc::new(Value::Str(value))),
+			Err(_) => Ok(Rc::new(Value::Nil))
+		},
+		_ => Err("getenv only takes string as argument".into())
+	}
+}
+
+pub fn setenv(args: Vec<Rc<Value>>) -> EvalResult {
+	if args.len() != 2 {
+		return Err("setenv takes a name and a value".into());
+	}
+
+	match *args[0] {
+		Value::Str(ref name) if !name.is_empty() && !name.contains('=') && !name.contains('\0') => {
+			let value = args[1].to_string();
+			if value.contains('\0') {
+				return Err("setenv: value contains a NUL character".into());
+			}
+			env::set_var(name, value);
+			Ok(Rc::new(Value::Nil))
+		},
+		_ => Err("setenv takes a valid variable name as first argument".into())
+	}
+}
+
+pub fn exit(args: Vec<Rc<Value>>) -> EvalResult {
+	let code = match args.len() {
+		0 => 0,
+		1 => match *args[0] {
+			Value::Int(code) => code as i32,
+			_ => { return Err("exit only takes int as argument".into()); }
+		},
+		_ => { return Err("exit takes at most one argument of type int".into()); }
+	};
+	io::stdout().flush().ok();
+	process::exit(code);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn environment() {
+		let name = Rc::new(Value::Str("LIAL_TEST_SETENV".to_string()));
+		assert_eq!(getenv(vec![name.clone()]), Ok(Rc::new(Value::Nil)));
+		setenv(vec![name.clone(), Rc::new(Value::Int(42))]).unwrap();
+		assert_eq!(getenv(vec![name.clone()]), Ok(Rc::new(Value::Str("42".to_string()))));
+		assert!(setenv(vec![Rc::new(Value::Str("A=B".to_string())), name]).is_err());
+	}
+}
@@ -0,0 +1,44 @@
+use std::rc::Rc;
+
+use interpreter::{Interpreter, Backend, Value, EvalResult};
+
+// Helpers shared by the tests of all modules.
+
+// Evaluates source in a new interpreter.
+pub fn eval(source: &str) -> EvalResult {
+	Interpreter::new().eval_str(source)
+}
+
+// The printed value of source, evaluated in a new interpreter.
+pub fn printed(source: &str) -> String {
+	eval(source).unwrap().to_string()
+}
+
+pub fn int(i: i64) -> EvalResult {
+	Ok(Rc::new(Value::Int(i)))
+}
+
+pub fn ints(values: &[i64]) -> EvalResult {
+	Ok(Rc::new(Value::List(values.iter().map(|&i| Rc::new(Value::Int(i))).collect())))
+}
+
+pub fn boolean(value: bool) -> EvalResult {
+	Ok(Rc::new(Value::Bool(value)))
+}
+
+pub fn string(s: &str) -> Rc<Value> {
+	Rc::new(Value::Str(s.to_string()))
+}
+
+// Calls test once for every backend with an `eval` that evaluates in one
+// interpreter of that backend and returns the printed value.
+pub fn for_each_backend<F: FnMut(&mut dyn FnMut(&str) -> Result<String, String>)>(mut test: F) {
+	for backend in [Backend::TreeWalker, Backend::Vm] {
+		let mut interpreter = Interpreter::with_backend(backend);
+		test(&mut |source: &str| interpreter.eval_str(source).map(|value| value.to_string()).map_err(|e| e.to_string()));
+	}
+}
+
+pub fn ok(printed: &str) -> Result<String, String> {
+	Ok(printed.to_string())
+}
@@ -1,32 +1,49 @@
+// Kept in the style of the original tokenizer.
+#![allow(clippy::is_digit_ascii_radix, clippy::len_zero, clippy::manual_range_contains,
+	clippy::needless_return, clippy::ptr_arg, clippy::redundant_field_names,
+	clippy::while_let_loop)]
+
 use std::str::Chars;
 use std::iter::Peekable;
 use std::collections::HashMap;
 
+use symbol::Symbol;
+
 #[derive(PartialEq, Debug, Clone)]
 pub enum Token {
 	LeftBracket,
 	RightBracket,
 	LeftCurlyBracket,
 	RightCurlyBracket,
+	// `#{`, the start of a set
+	HashCurlyBracket,
 	Colon,
+	// `@x`, short for `(deref x)`
+	At,
 
 	Str(String),
 	Int(i64),
 	Real(f64),
 	Bool(bool),
-	Symbol(String),
+	Symbol(Symbol),
+	Keyword(String),
 	Nil
 }
 
 pub type TokenizerResult = Result<Token, String>;
 
+pub const UNTERMINATED_STRING: &str = "unexpected end of string";
+
 pub struct Tokenizer<'a> {
 	chars: Peekable<Chars<'a>>,
-	line: i32,
-	reserved: HashMap<String, Token>
+	line: usize,
+	column: usize,
+	start: (usize, usize),
+	reserved: HashMap<String, Token>,
+	after_symbol: bool
 }
 
-fn is_symbolchar(c: char) -> bool {
+pub fn is_symbolchar(c: char) -> bool {
 	return !(c == '(' || c == ')' || c == '{' || c == '}' || c == ';' || c == ':') && (c >= '!' && c <= '~');
 }
 
@@ -37,11 +54,40 @@ impl<'a> Tokenizer<'a> {
 		reserved.insert("false".to_string(), Token::Bool(false));
 		reserved.insert("nil".to_string(), Token::Nil);
 
-		Tokenizer {
+		let mut tokenizer = Tokenizer {
 			chars: string.chars().peekable(),
-			line: 0,
-			reserved: reserved
+			line: 1,
+			column: 1,
+			start: (1, 1),
+			reserved: reserved,
+			after_symbol: false
+		};
+
+		// skip the shebang line of executable scripts
+		if string.starts_with("#!") {
+			while tokenizer.chars.peek().is_some() && tokenizer.chars.peek() != Some(&'\n') {
+				tokenizer.bump();
+			}
 		}
+		tokenizer
+	}
+
+	// consumes the next character and keeps track of the position
+	fn bump(&mut self) -> Option<char> {
+		let c = self.chars.next();
+		if c == Some('\n') {
+			self.line += 1;
+			self.column = 1;
+		} else if c.is_some() {
+			self.column += 1;
+		}
+		c
+	}
+
+	// line and column of the start of the last token (or error), the end of
+	// the source once all tokens are consumed
+	pub fn position(&self) -> (usize, usize) {
+		self.start
 	}
 
 	/*pub fn collect(&mut self) -> Result<Vec<Token>, String> {
@@ -56,7 +102,7 @@ impl<'a> Tokenizer<'a> {
 	}*/
 
 	fn read_int(&mut self, base: u32) -> TokenizerResult {
-		self.chars.next().unwrap(); // consume 'x', 'o' or 'b'
+		self.bump().unwrap(); // consume 'x', 'o' or 'b'
 
 		let mut buf: Vec<u32> = vec![];
 		loop {
@@ -67,7 +113,7 @@ impl<'a> Tokenizer<'a> {
 			} else {
 				break;
 			}
-			buf.push( self.chars.next().unwrap().to_digit(base).unwrap() );
+			buf.push( self.bump().unwrap().to_digit(base).unwrap() );
 		}
 
 		if buf.len() == 0 {
@@ -88,37 +134,59 @@ impl<'a> Tokenizer<'a> {
 impl<'a> Iterator for Tokenizer<'a> {
 	type Item = TokenizerResult;
 	fn next(&mut self) -> Option<TokenizerResult> {
-		if let Some(ch) = self.chars.next() {
+		let after_symbol = self.after_symbol;
+		self.after_symbol = false;
+		self.start = (self.line, self.column);
+		if let Some(ch) = self.bump() {
 			match ch {
 				';' => {
-					let mut c = self.chars.next();
+					let mut c = self.bump();
 					while c.is_some() && c != Some('\n') {
-						c = self.chars.next();
+						c = self.bump();
 					}
-					self.line += 1;
-					self.next()
-				},
-				'\n' => {
-					self.line += 1;
 					self.next()
 				},
+				'\n' => self.next(),
 				'(' => Some(Ok(Token::LeftBracket)),
 				')' => Some(Ok(Token::RightBracket)),
 				'{' => Some(Ok(Token::LeftCurlyBracket)),
 				'}' => Some(Ok(Token::RightCurlyBracket)),
-				':' => Some(Ok(Token::Colon)),
+				'#' if self.chars.peek() == Some(&'{') => {
+					self.bump().unwrap();
+					Some(Ok(Token::HashCurlyBracket))
+				},
+				'@' => Some(Ok(Token::At)),
+				':' => {
+					// `a: 1` is a map entry, `:a` (not directly after a symbol) a keyword
+					let is_keyword = match self.chars.peek() {
+						Some(c) => !after_symbol && is_symbolchar(*c),
+						None => false
+					};
+					if !is_keyword {
+						return Some(Ok(Token::Colon));
+					}
+
+					let mut buf: String = String::new();
+					while let Some(peek) = self.chars.peek().cloned() {
+						if !is_symbolchar(peek) {
+							break;
+						}
+						buf.push( self.bump().unwrap() );
+					}
+					Some(Ok(Token::Keyword(buf)))
+				},
 				'"' => {
 					let mut buf: String = String::new();
 					loop {
-						if let Some(c) = self.chars.next() {
+						if let Some(c) = self.bump() {
 							match c {
 								'"' => {
 									break;
 								},
 								'\\' => {
-									let escaped = self.chars.next();
+									let escaped = self.bump();
 									if escaped.is_none() {
-										return Some(Err("unexpected end of string".to_string()));
+										return Some(Err(UNTERMINATED_STRING.to_string()));
 									}
 									match escaped.unwrap() {
 										'n' => buf.push('\n'),
@@ -134,7 +202,7 @@ impl<'a> Iterator for Tokenizer<'a> {
 								_ => buf.push(c)
 							}
 						} else {
-							return Some(Err("unexpected end of string".to_string()));
+							return Some(Err(UNTERMINATED_STRING.to_string()));
 						}
 					}
 					Some(Ok(Token::Str(buf)))
@@ -149,7 +217,7 @@ impl<'a> Iterator for Tokenizer<'a> {
 
 					return match c {
 						'.' => {
-							self.chars.next().unwrap();
+							self.bump().unwrap();
 							let mut buf: String = "0.".to_string();
 							loop {
 								if let Some(peek) = self.chars.peek() {
@@ -160,7 +228,7 @@ impl<'a> Iterator for Tokenizer<'a> {
 									break;
 								}
 
-								buf.push( self.chars.next().unwrap() );
+								buf.push( self.bump().unwrap() );
 							}
 							return match buf.parse::<f64>() {
 								Ok(number) => Some(Ok(Token::Real(number))),
@@ -173,7 +241,7 @@ impl<'a> Iterator for Tokenizer<'a> {
 						_ => Some(Ok(Token::Int(0))) // illegal number litteral?
 					};
 				},
-				'1'...'9' => {
+				'1'..='9' => {
 					let mut is_real = false;
 					let mut buf: String = ch.to_string();
 					loop {
@@ -189,7 +257,7 @@ impl<'a> Iterator for Tokenizer<'a> {
 							break;
 						}
 
-						buf.push( self.chars.next().unwrap() );
+						buf.push( self.bump().unwrap() );
 					}
 
 					if is_real {
@@ -223,13 +291,14 @@ impl<'a> Iterator for Tokenizer<'a> {
 							break;
 						}
 
-						buf.push( self.chars.next().unwrap() );
+						buf.push( self.bump().unwrap() );
 					}
 
 					if let Some(token) = self.reserved.get(&buf) {
 						Some(Ok(token.clone()))
 					} else {
-						Some(Ok(Token::Symbol(buf)))
+						self.after_symbol = self.chars.peek() == Some(&':');
+						Some(Ok(Token::Symbol(Symbol::intern(&buf))))
 					}
 				}
 			}
@@ -273,6 +342,57 @@ mod tests {
 		assert_eq!(tokens.next(), None);
 	}
 
+	#[test]
+	fn shebang() {
+		let raw = "#!/usr/bin/env lial\n(echo)".to_string();
+		let mut tokens = Tokenizer::new(&raw);
+		assert_eq!(tokens.next().unwrap(), Ok(Token::LeftBracket));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::Symbol(Symbol::intern("echo"))));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::RightBracket));
+		assert_eq!(tokens.next(), None);
+	}
+
+	#[test]
+	fn sets() {
+		let raw = " #{ 1 } #a ".to_string();
+		let mut tokens = Tokenizer::new(&raw);
+		assert_eq!(tokens.next().unwrap(), Ok(Token::HashCurlyBracket));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::Int(1)));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::RightCurlyBracket));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::Symbol(Symbol::intern("#a"))));
+		assert_eq!(tokens.next(), None);
+	}
+
+	#[test]
+	fn deref() {
+		let raw = " @a b@c ".to_string();
+		let mut tokens = Tokenizer::new(&raw);
+		assert_eq!(tokens.next().unwrap(), Ok(Token::At));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::Symbol(Symbol::intern("a"))));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::Symbol(Symbol::intern("b@c"))));
+		assert_eq!(tokens.next(), None);
+	}
+
+	#[test]
+	fn keywords() {
+		let raw = " { a: 1 } :only {:} { b :c } ".to_string();
+		let mut tokens = Tokenizer::new(&raw);
+		assert_eq!(tokens.next().unwrap(), Ok(Token::LeftCurlyBracket));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::Symbol(Symbol::intern("a"))));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::Colon));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::Int(1)));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::RightCurlyBracket));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::Keyword("only".to_string())));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::LeftCurlyBracket));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::Colon));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::RightCurlyBracket));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::LeftCurlyBracket));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::Symbol(Symbol::intern("b"))));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::Keyword("c".to_string())));
+		assert_eq!(tokens.next().unwrap(), Ok(Token::RightCurlyBracket));
+		assert_eq!(tokens.next(), None);
+	}
+
 }
 
 
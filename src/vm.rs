@@ -0,0 +1,366 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use im_rc::Vector;
+
+use compiler::{self, Op, Proto, Capture};
+use interpreter::{Interpreter, Value, Lambda, EvalResult};
+use parser::Node;
+use collections::{Map, Set};
+
+// A compiled function together with the values it captured.
+#[derive(Debug)]
+pub struct Closure {
+	pub proto: Rc<Proto>,
+	pub captured: Vec<Rc<Value>>
+}
+
+struct Frame {
+	closure: Rc<Closure>,
+	ip: usize,
+	// position of the first parameter on the stack
+	base: usize,
+	// the number of arguments passed
+	argc: usize,
+	// namespace of the caller, restored on return
+	ns: Option<Rc<String>>
+}
+
+struct Vm {
+	stack: Vec<Rc<Value>>,
+	frames: Vec<Frame>
+}
+
+// Compiles and runs a top level expression.
+pub fn eval(interpreter: &mut Interpreter, node: &Rc<Node>) -> EvalResult {
+	let proto = compiler::compile(node)?;
+	let closure = Rc::new(Closure { proto: Rc::new(proto), captured: vec![] });
+	let ns = interpreter.namespace();
+	call(interpreter, closure, ns, vec![])
+}
+
+// Calls a compiled function (an arity that accepts args), `ns` is the
+// namespace it was defined in.
+pub fn call(interpreter: &mut Interpreter, closure: Rc<Closure>, ns: Option<Rc<String>>, args: Vec<Rc<Value>>) -> EvalResult {
+	let caller_ns = interpreter.swap_ns(ns);
+	let argc = args.len();
+	let mut vm = Vm {
+		stack: closure.proto.params.bind(args),
+		frames: vec![Frame { closure, ip: 0, base: 0, argc, ns: caller_ns.clone() }]
+	};
+	let res = vm.run(interpreter);
+	if res.is_err() {
+		interpreter.swap_ns(caller_ns);
+	}
+	res
+}
+
+impl Vm {
+	fn frame(&self) -> &Frame {
+		self.frames.last().unwrap()
+	}
+
+	fn pop(&mut self) -> Rc<Value> {
+		self.stack.pop().expect("vm: stack underflow")
+	}
+
+	// Returns from the running function, the result of the outermost one is
+	// returned.
+	fn ret(&mut self, interpreter: &mut Interpreter, value: Rc<Value>) -> Option<Rc<Value>> {
+		let frame = self.frames.pop().unwrap();
+		self.stack.truncate(frame.base);
+		interpreter.swap_ns(frame.ns);
+		if self.frames.is_empty() {
+			return Some(value);
+		}
+		self.stack.push(value);
+		None
+	}
+
+	fn run(&mut self, interpreter: &mut Interpreter) -> EvalResult {
+		loop {
+			let op = {
+				let frame = self.frames.last_mut().unwrap();
+				frame.ip += 1;
+				frame.closure.proto.code[frame.ip - 1]
+			};
+
+			match op {
+				Op::Const(index) => {
+					let value = self.frame().closure.proto.consts[index].clone();
+					self.stack.push(value);
+				},
+				Op::Local(slot) => {
+					let value = self.stack[self.frame().base + slot].clone();
+					self.stack.push(value);
+				},
+				Op::SetLocal(slot) => {
+					let value = self.pop();
+					let at = self.frame().base + slot;
+					self.stack[at] = value;
+				},
+				Op::Default(slot, target) => {
+					if slot < self.frame().argc {
+						self.frames.last_mut().unwrap().ip = target;
+					}
+				},
+				Op::Captured(index) => {
+					let value = self.frame().closure.captured[index].clone();
+					self.stack.push(value);
+				},
+				Op::Global(slot) => {
+					let value = interpreter.global(slot)?;
+					self.stack.push(value);
+				},
+				Op::Def(index) => {
+					let name = self.frame().closure.proto.names[index];
+					let value = self.pop();
+					let value = interpreter.define_qualified(name, value);
+					self.stack.push(value);
+				},
+				Op::SetGlobal(slot) => {
+					let value = self.stack.last().unwrap().clone();
+					interpreter.set_global(slot, value)?;
+				},
+				Op::Destructure => {
+					let closure = self.frame().closure.clone();
+					let params = &closure.proto.params;
+					let base = self.frame().base;
+					let values = params.destructure(&self.stack[base..base + params.slots()])?;
+					for (i, value) in values.into_iter().enumerate() {
+						self.stack[base + params.slots() + i] = value;
+					}
+				},
+				Op::Box(slot) => {
+					let at = self.frame().base + slot;
+					let value = self.stack[at].clone();
+					self.stack[at] = Rc::new(Value::Atom(Rc::new(RefCell::new(value))));
+				},
+				Op::Unbox => {
+					let value = match *self.pop() {
+						Value::Atom(ref cell) => cell.borrow().clone(),
+						_ => { return Err("vm: expected a box".into()); }
+					};
+					self.stack.push(value);
+				},
+				Op::Store => {
+					let value = self.pop();
+					match *self.pop() {
+						Value::Atom(ref cell) => { cell.replace(value.clone()); },
+						_ => { return Err("vm: expected a box".into()); }
+					}
+					self.stack.push(value);
+				},
+				Op::List(len) => {
+					let at = self.stack.len() - len;
+					let list: Vector<Rc<Value>> = self.stack.drain(at..).collect();
+					self.stack.push(Rc::new(Value::List(list)));
+				},
+				Op::Set(len) => {
+					let at = self.stack.len() - len;
+					let set: Set<Rc<Value>> = self.stack.drain(at..).collect();
+					self.stack.push(Rc::new(Value::Set(set)));
+				},
+				Op::Map(index) => {
+					let closure = self.frame().closure.clone();
+					let keys = &closure.proto.keys[index];
+					let at = self.stack.len() - keys.len();
+					let map: Map<String, Rc<Value>> = keys.iter().cloned()
+						.zip(self.stack.drain(at..)).collect();
+					self.stack.push(Rc::new(Value::Map(map)));
+				},
+				Op::Closure(first, count) => {
+					let mut closures: Vec<Rc<Closure>> = vec![];
+					for index in first..first + count {
+						let proto = self.frame().closure.proto.protos[index].clone();
+						let captured: Vec<Rc<Value>> = proto.captures.iter().map(|capture| match *capture {
+							Capture::Local(slot) => self.stack[self.frame().base + slot].clone(),
+							Capture::Captured(index) => self.frame().closure.captured[index].clone()
+						}).collect();
+						closures.push(Rc::new(Closure { proto, captured }));
+					}
+					let lambda = Lambda::compiled(closures, interpreter.namespace());
+					self.stack.push(Rc::new(Value::Func(lambda)));
+				},
+				Op::Call(argc) | Op::TailCall(argc) => {
+					let at = self.stack.len() - argc - 1;
+					let callee = self.stack[at].clone();
+					if let Value::Func(ref lambda) = *callee {
+						let arity = lambda.arity(argc)?;
+						if let Some(ref closure) = arity.code {
+							if !closure.proto.params.is_simple() {
+								let args = self.stack.split_off(at + 1);
+								self.stack.extend(closure.proto.params.bind(args));
+							}
+							if let Op::TailCall(_) = op {
+								// reuse the frame of the running function
+								let args = self.stack.split_off(at + 1);
+								let frame = self.frames.last_mut().unwrap();
+								self.stack.truncate(frame.base);
+								self.stack.extend(args);
+								frame.closure = closure.clone();
+								frame.ip = 0;
+								frame.argc = argc;
+								interpreter.swap_ns(lambda.ns.clone());
+							} else {
+								self.stack.remove(at);
+								let ns = interpreter.swap_ns(lambda.ns.clone());
+								self.frames.push(Frame { closure: closure.clone(), ip: 0, base: at, argc, ns });
+							}
+							continue;
+						}
+					}
+
+					// builtins and functions of the tree-walker
+					let args = self.stack.split_off(at + 1);
+					self.stack.pop();
+					let value = interpreter.apply(callee, args)?;
+					if let Op::TailCall(_) = op {
+						if let Some(value) = self.ret(interpreter, value) {
+							return Ok(value);
+						}
+					} else {
+						self.stack.push(value);
+					}
+				},
+				Op::Jump(target) => {
+					self.frames.last_mut().unwrap().ip = target;
+				},
+				Op::JumpIfFalse(target) => {
+					if !self.pop().to_bool() {
+						self.frames.last_mut().unwrap().ip = target;
+					}
+				},
+				Op::JumpIfTrue(target) => {
+					if self.pop().to_bool() {
+						self.frames.last_mut().unwrap().ip = target;
+					}
+				},
+				Op::Pop => {
+					self.pop();
+				},
+				Op::Return => {
+					let value = self.pop();
+					if let Some(value) = self.ret(interpreter, value) {
+						return Ok(value);
+					}
+				},
+				Op::Try(catches, finally) => {
+					let cleanup = if finally { Some(self.pop()) } else { None };
+					let at = self.stack.len() - 2 * catches;
+					let mut handlers = self.stack.split_off(at).into_iter();
+					let mut pairs: Vec<(Rc<Value>, Rc<Value>)> = vec![];
+					while let (Some(kind), Some(handler)) = (handlers.next(), handlers.next()) {
+						pairs.push((kind, handler));
+					}
+					let body = self.pop();
+					let value = interpreter.try_catch(body, pairs, cleanup)?;
+					self.stack.push(value);
+				},
+				Op::Match(clauses) => {
+					let at = self.stack.len() - 2 * clauses;
+					let mut clauses = self.stack.split_off(at).into_iter();
+					let mut pairs: Vec<(Rc<Value>, Rc<Value>)> = vec![];
+					while let (Some(body), Some(guard)) = (clauses.next(), clauses.next()) {
+						pairs.push((body, guard));
+					}
+					let value = self.pop();
+					let body = interpreter.select_clause(&value, pairs)?;
+					self.stack.push(body);
+					self.stack.push(value);
+				},
+				Op::Apply(argc) => {
+					let at = self.stack.len() - argc;
+					let args = self.stack.split_off(at);
+					let func = self.pop();
+					let value = interpreter.apply_list(func, args)?;
+					self.stack.push(value);
+				},
+				Op::Eval(index) => {
+					let node = self.frame().closure.proto.nodes[index].clone();
+					let value = interpreter.eval_node(node)?;
+					self.stack.push(value);
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use interpreter::Backend;
+
+	// every program is run by both backends, the tree-walker is the reference
+	const PROGRAMS: &[&str] = &[
+		"(+ 1 2 (* 3 4))",
+		"(def x 10) (if (= x 10) :yes :no)",
+		"(if nil 1)",
+		"{ (and 1 true) (and 1 nil) (and) (or nil 2) (or nil false) (or) }",
+		"(do (def a 1) (def b (+ a 1)) { a b { c: (+ a b) } })",
+		"(def seen (atom {})) (defn see {x} (swap! seen conj x) x) { b: (see :b) c: (see :c) a: (see :a) } @seen",
+		"(defn adder {n} (fn {x} (+ x n))) ((adder 10) 5)",
+		"(defn curry3 {a} (fn {b} (fn {c} { a b c }))) (((curry3 1) 2) 3)",
+		"(defn twice {f} (fn {x} (f (f x)))) ((twice (partial + 3)) 1)",
+		"((comp inc inc) 40)",
+		"{ ((comp list inc +) 1 2 3) ((partial list 1 2) 3 4) }",
+		"{ (apply + (list 1 2)) (apply list 1 2 { 3 4 }) (apply (fn {a & b} b) 1 {}) }",
+		"(apply + 1 2)",
+		"(apply +)",
+		"(cond false 1 (= 1 2) 2 :else 3)",
+		"(when true (def y 2) (* y y))",
+		"(defmacro swap-args {f a b} (f b a)) (swap-args - 1 10)",
+		"(defn fib {n} (if (= n 0) 0 (if (= n 1) 1 (+ (fib (- n 1)) (fib (- n 2)))))) (fib 15)",
+		"(defn count {n acc} (if (zero? n) acc (count (dec n) (+ acc n)))) (count 50 0)",
+		"(defn f {x} \"Doc.\" x) (f { 1 2 })",
+		"(defn acc {n} (set! n (+ n 1)) ((fn {} (set! n (* n 2)) { n ((fn {} n)) }))) (acc 1)",
+		"(defn sum {n acc} (set! acc (+ acc n)) (if (zero? n) acc (sum (dec n) acc))) (sum 10 0)",
+		"(def g 1) (defn h {} (set! g { g })) (h) g",
+		"(defn opt {a (b (+ a 1)) & more} { a b more }) { (opt 1) (opt 1 5) (opt 1 5 6 7) }",
+		"(defn arities ({} 0) ({x} (arities)) ({x y & zs} (+ x y (count zs)))) { (arities) (arities 1) (arities 1 2 3 4) }",
+		"(defn f {a (h (fn {} a))} (set! a 5) (h)) (f 1)",
+		"(defn f {a (h (fn {} (set! a 2)))} (h) a) (f 1)",
+		"(defn f {a (b (set! a (inc a))) & more} (set! more a) { a b more }) { (f 1) (f 1 0) }",
+		"(defn loop {n (acc 0)} (if (zero? n) acc (loop (dec n) (+ acc n)))) (loop 100)",
+		"(defn pair {{a b} { k: {c & d} }} (set! a (+ a c)) (fn {} { a b d })) ((pair { 1 2 } { k: { 3 4 } }))",
+		"(let {x 1 {y z} { x 2 }} (let {w (+ y z)} { x w }))",
+		"((fn {{a b}} a) { 1 })",
+		"(defn len {xs (n 0)} (match xs {} n {_ & more} (len more (inc n)))) (len { 1 2 3 })",
+		"(defn kind {x} (match x { type: t } :when (= t :a) :a { type: _ } :typed _ :other)) { (kind { type: :a }) (kind { type: :b }) (kind 1) }",
+		"(match 5 1 :one)",
+		"((fn {a} a) 1 2)",
+		"undefined-symbol",
+		"(1 2)",
+		"()",
+		"(def)"
+	];
+
+	fn run(backend: Backend, source: &str) -> EvalResult {
+		Interpreter::with_backend(backend).eval_str(source)
+	}
+
+	#[test]
+	fn backends() {
+		for source in PROGRAMS {
+			assert_eq!(run(Backend::Vm, source), run(Backend::TreeWalker, source), "{}", source);
+		}
+	}
+
+	#[test]
+	fn tail_calls() {
+		let source = "(defn count {n acc} (if (zero? n) acc (count (dec n) (+ acc 1)))) (count 100000 0)";
+		assert_eq!(run(Backend::Vm, source), Ok(Rc::new(Value::Int(100000))));
+	}
+
+	#[test]
+	fn mixed() {
+		let mut interpreter = Interpreter::new();
+		interpreter.eval_str("(defn walker {f x} (f x))").unwrap();
+		interpreter.set_backend(Backend::Vm);
+		interpreter.eval_str("(defn compiled {x} (walker inc x))").unwrap();
+		interpreter.set_backend(Backend::TreeWalker);
+		assert_eq!(interpreter.eval_str("(compiled 41)"), Ok(Rc::new(Value::Int(42))));
+		assert_eq!(interpreter.doc("compiled"), Some("(compiled x)".to_string()));
+		assert_eq!(interpreter.eval_str("(compiled)"), Err("`compiled` takes 1 argument, got 0".into()));
+	}
+}